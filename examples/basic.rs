@@ -23,7 +23,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     slides(|b| {
 
-        b.slide("a slide with just text", |s| {
+        b.theme(Theme::dark())
+            .syntax(include_str!("syntaxes/toml.sublime-syntax"))
+            .title_slide("bema", "slideshows in rust", "the bema authors", "")
+            .slide("a slide with just text", |s| {
             s.text("text in the first slide")
             .text("")
             .t("t is an alias for text")
@@ -146,6 +149,43 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             })
         })
 
+        .slide("tables", |s| {
+            s.table(&["language", "typing", "year"], &[Align::Left, Align::Center, Align::Right], &[
+                &["ruby", "dynamic", "1995"],
+                &["python", "dynamic", "1991"],
+                &["rust", "static", "2015"],
+            ])
+        })
+
+        .slide("custom syntax", |s| {
+            s.code("toml", indoc! {r#"
+                [package]
+                name = "bema" # a comment
+                edition = "2018"
+            "#})
+        })
+
+        .slide("inline styles", |s| {
+            s.text("**bold**, *italic*, __underline__ and `inline code`")
+                .text("[red]{red}, [green]{green} or [any colour]{#ff8800}")
+        })
+
+        .slide("step by step", |s| {
+            s.text("first")
+                .pause()
+                .text("then")
+                .pause()
+                .text("and finally")
+        })
+
+        .section("layouts")
+
+        .two_columns("two columns", |l| l.text("on the left"), |r| r.code("rs", "let right = true;"))
+
+        .image_slide(include_bytes!("capybara.jpg").to_vec(), ".jpg")
+
+        .closing("thanks!", |s| s.text("questions?"))
+
     }).run()?;
 
     Ok(())
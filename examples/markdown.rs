@@ -0,0 +1,6 @@
+extern crate bema;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    bema::load("examples/deck.md")?.run()?;
+    Ok(())
+}
@@ -1,21 +1,130 @@
+use std::path::PathBuf;
+use crate::theme::Theme;
+
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+// where the content of a slide sits between its index line and the bottom of the screen
+#[derive(Clone, Copy, PartialEq)]
+pub enum VAlign {
+    Top,
+    Middle,
+    Bottom,
+}
+
+// how large an image is drawn; lengths are pixels of a 1280 pixels wide slide and percentages
+// are of the width the image is laid out in or of the slide height below the title
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImageSize {
+    // the size of the image itself
+    Natural,
+    Width(usize),
+    Height(usize),
+    WidthPercent(f64),
+    HeightPercent(f64),
+    // as large as it can be while showing all of the image
+    Contain,
+    // filling all of the available space, what overflows being cropped
+    Cover,
+}
+
+impl From<Option<usize>> for ImageSize {
+    fn from(width: Option<usize>) -> ImageSize {
+        width.map(ImageSize::Width).unwrap_or(ImageSize::Natural)
+    }
+}
 
 #[derive(Clone)]
 pub enum SlideItem {
     Code{ extension: String, source: String },
-    Image{ image: Vec<u8>, extension: String, width: Option<usize> },
+    Image{ image: Vec<u8>, extension: String, size: ImageSize },
     Text{ text: String },
     Rows { items: Vec<SlideItem> },
     Cols { items: Vec<SlideItem> },
     Framed { items: Vec<SlideItem> },
+    // items placed to the left, centre or right of the width they are laid out in
+    Aligned { align: Align, items: Vec<SlideItem> },
+    Table { header: Vec<String>, rows: Vec<Vec<String>>, align: Vec<Align> },
+    Pause,
+}
+
+// what happens to code lines wider than the slide
+#[derive(Clone, Copy, PartialEq)]
+pub enum CodeOverflow {
+    // lines run off the edge
+    Overflow,
+    // lines go on below, each continuation starting with a `↪` marker
+    Wrap,
+    // lines are cut and end with an `…`
+    Truncate,
+}
+
+// how a slide is laid out, given by the `Bema` builder that added it
+#[derive(Clone, PartialEq)]
+pub enum Layout {
+    // the index, the title and the items below it
+    Content,
+    // the title of the deck with a subtitle and a byline of its author and date
+    Title { subtitle: String, byline: String },
+    // a title alone, opening a part of the deck
+    Section,
+    // the items without index, title or footer, a `Cover` image filling the whole slide
+    Image,
+    // a title with a few items, such as thanks and contacts
+    Closing,
+}
+
+// extra grammars and themes for code highlighting, on top of the syntect defaults
+#[derive(Clone)]
+pub enum HighlightSource {
+    // the source of a .sublime-syntax grammar
+    Syntax(String),
+    // a .tmTheme file, named to be used as a theme `code_theme`
+    CodeTheme { name: String, tm_theme: Vec<u8> },
+    // a directory of .sublime-syntax and .tmTheme files, themes being named after their file
+    Dir(PathBuf),
 }
 
 #[derive(Clone)]
 pub struct Slide {
     pub title: String,
     pub items: Vec<SlideItem>,
+    pub notes: String,
+    // applies to the title and to the items not in an `Aligned` container
+    pub align: Align,
+    pub valign: VAlign,
+    pub layout: Layout,
 }
 
+// what the deck is about, used by the header and footer templates and by the exports
+#[derive(Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub title: String,
+    pub author: String,
+    pub date: String,
+    pub event: String,
+    pub license: String,
+}
+
+// the slide counter that used to be the only header
+pub const DEFAULT_HEADER: &str = "{n}/{total}";
+
 #[derive(Clone)]
 pub struct Bema {
-    pub slides: Vec<Slide>
+    pub slides: Vec<Slide>,
+    pub source: Option<PathBuf>,
+    pub theme: Theme,
+    // the theme switched to with the theme toggle key
+    pub alternate_theme: Theme,
+    pub highlighting: Vec<HighlightSource>,
+    pub code_overflow: CodeOverflow,
+    pub metadata: Metadata,
+    // templates drawn above and below every slide, see `runner::chrome`
+    pub header: String,
+    pub footer: String,
 }
@@ -0,0 +1,308 @@
+use crossterm::style::Color;
+use crate::measure;
+use crate::bema::Align;
+
+pub const BORDER_UP: u8 = 1;
+pub const BORDER_DOWN: u8 = 2;
+pub const BORDER_LEFT: u8 = 4;
+pub const BORDER_RIGHT: u8 = 8;
+
+fn border_symbol(border: u8) -> char {
+    const UP_DOWN: u8 = BORDER_UP | BORDER_DOWN;
+    const LEFT_RIGHT: u8 = BORDER_LEFT | BORDER_RIGHT;
+    const DOWN_RIGHT: u8 = BORDER_DOWN | BORDER_RIGHT;
+    const DOWN_LEFT: u8 = BORDER_DOWN | BORDER_LEFT;
+    const UP_RIGHT: u8 = BORDER_UP | BORDER_RIGHT;
+    const UP_LEFT: u8 = BORDER_UP | BORDER_LEFT;
+    const UP_DOWN_RIGHT: u8 = UP_DOWN | BORDER_RIGHT;
+    const UP_DOWN_LEFT: u8 = UP_DOWN | BORDER_LEFT;
+    const LEFT_RIGHT_DOWN: u8 = LEFT_RIGHT | BORDER_DOWN;
+    const LEFT_RIGHT_UP: u8 = LEFT_RIGHT | BORDER_UP;
+    const ALL: u8 = UP_DOWN | LEFT_RIGHT;
+    match border {
+        DOWN_RIGHT => '┌',
+        DOWN_LEFT => '┐',
+        UP_RIGHT => '└',
+        UP_LEFT => '┘',
+        UP_DOWN_RIGHT => '├',
+        UP_DOWN_LEFT => '┤',
+        LEFT_RIGHT_DOWN => '┬',
+        LEFT_RIGHT_UP => '┴',
+        ALL => '┼',
+        b if b & LEFT_RIGHT == 0 => '│',
+        _ => '─',
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Cell {
+    pub symbol: char,
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    // box-drawing line directions, so that touching borders can be joined
+    pub border: u8,
+}
+
+impl Cell {
+    pub fn new(symbol: char) -> Cell {
+        Cell { symbol, fg: None, bg: None, bold: false, italic: false, underline: false, border: 0 }
+    }
+
+    pub fn blank() -> Cell {
+        Cell::new(' ')
+    }
+
+    pub fn border(border: u8, fg: Option<Color>) -> Cell {
+        Cell { fg, border, ..Cell::new(border_symbol(border)) }
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.symbol == ' ' && self.bg.is_none()
+    }
+
+    // the second cell of a wide character, covered by it and never printed
+    pub fn is_wide_tail(&self) -> bool {
+        self.symbol == '\0'
+    }
+}
+
+// the cells of some text styled like `style`, wide characters taking two cells and zero width ones none
+pub fn text_cells(text: &str, style: &Cell) -> Vec<Cell> {
+    let mut cells = vec![];
+    for symbol in text.chars() {
+        let columns = measure::char_columns(symbol);
+        if columns > 0 {
+            cells.push(Cell { symbol, ..style.clone() });
+        }
+        if columns > 1 {
+            cells.push(Cell { symbol: '\0', ..style.clone() });
+        }
+    }
+    cells
+}
+
+// an image drawn over the cells of a buffer by the terminal graphics protocol
+#[derive(Clone, PartialEq)]
+pub struct ImagePlacement {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub png: Vec<u8>,
+}
+
+// a block of terminal lines at most `width` cells wide, rendered off-screen
+// so that it can be laid out next to other blocks before being printed
+#[derive(Clone)]
+pub struct CellBuffer {
+    pub width: usize,
+    pub lines: Vec<Vec<Cell>>,
+    pub images: Vec<ImagePlacement>,
+    // the buffer is exactly a box drawn around its content
+    pub boxed: bool,
+}
+
+impl CellBuffer {
+    pub fn new(width: usize) -> CellBuffer {
+        CellBuffer { width, lines: vec![], images: vec![], boxed: false }
+    }
+
+    pub fn height(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn push_line(&mut self, mut cells: Vec<Cell>) {
+        cells.truncate(self.width);
+        self.lines.push(cells);
+    }
+
+    pub fn push_text(&mut self, offset: usize, text: &str, fg: Option<Color>, bg: Option<Color>, bold: bool) {
+        let mut cells = vec![Cell::blank(); offset];
+        cells.extend(text_cells(text, &Cell { fg, bg, bold, ..Cell::blank() }));
+        self.push_line(cells);
+    }
+
+    // stacks `other` below this buffer, centering it when it is narrower
+    pub fn append(&mut self, other: CellBuffer) {
+        let x = self.width.saturating_sub(other.width) / 2;
+        self.place(other, x, self.height());
+    }
+
+    // a buffer `width` wide with this one aligned in it, kept as it is when centred so that boxes can still be packed
+    pub fn aligned(self, width: usize, align: Align) -> CellBuffer {
+        if align == Align::Center || self.width >= width {
+            return self;
+        }
+        let x = measure::offset(align, (width - self.width) as f64) as usize;
+        let mut buffer = CellBuffer::new(width);
+        buffer.place(self, x, 0);
+        buffer
+    }
+
+    // draws `other` with its top left corner at (x, y), growing this buffer as needed
+    pub fn place(&mut self, other: CellBuffer, x: usize, y: usize) {
+        while self.lines.len() < y + other.lines.len() {
+            self.lines.push(vec![]);
+        }
+        for (k, line) in other.lines.into_iter().enumerate() {
+            let target = &mut self.lines[y + k];
+            for (dx, cell) in line.into_iter().enumerate() {
+                if x + dx >= self.width {
+                    break;
+                }
+                while target.len() <= x + dx {
+                    target.push(Cell::blank());
+                }
+                let previous = &target[x + dx];
+                target[x + dx] = if cell.border != 0 && previous.border != 0 {
+                    Cell::border(cell.border | previous.border, cell.fg)
+                } else {
+                    cell
+                };
+            }
+        }
+        for image in other.images {
+            self.images.push(ImagePlacement { x: image.x + x, y: image.y + y, ..image });
+        }
+    }
+
+    // first and last columns used by the content
+    fn content_bounds(&self) -> (usize, usize) {
+        let cells = self.lines.iter().flat_map(|line| {
+            let first = line.iter().position(|c| !c.is_blank());
+            let last = line.iter().rposition(|c| !c.is_blank());
+            first.zip(last).map(|(first, last)| (first, last + 1))
+        });
+        let images = self.images.iter().map(|image| (image.x, image.x + image.width));
+        cells.chain(images)
+            .fold(None, |bounds: Option<(usize, usize)>, (start, end)| match bounds {
+                Some((s, e)) => Some((s.min(start), e.max(end))),
+                None => Some((start, end)),
+            })
+            .unwrap_or((0, 0))
+    }
+
+    fn crop(self, start: usize, end: usize) -> CellBuffer {
+        let mut buffer = CellBuffer::new(end - start);
+        for line in self.lines {
+            buffer.push_line(line.into_iter().skip(start).collect());
+        }
+        for image in self.images {
+            buffer.images.push(ImagePlacement { x: image.x - start, ..image });
+        }
+        buffer
+    }
+
+    // draws a box around the content, sized to the content rather than to the buffer
+    pub fn framed(self, fg: Option<Color>) -> CellBuffer {
+        let (start, end) = self.content_bounds();
+        let content = self.crop(start, end);
+        let width = content.width + 4;
+        let mut buffer = CellBuffer::new(width);
+        buffer.boxed = true;
+        let horizontal = vec![Cell::border(BORDER_LEFT | BORDER_RIGHT, fg); width - 2];
+        let mut top = vec![Cell::border(BORDER_RIGHT | BORDER_DOWN, fg)];
+        top.extend(horizontal.iter().cloned());
+        top.push(Cell::border(BORDER_LEFT | BORDER_DOWN, fg));
+        buffer.push_line(top);
+        for _ in 0..content.height() {
+            buffer.push_line(vec![]);
+        }
+        let mut bottom = vec![Cell::border(BORDER_RIGHT | BORDER_UP, fg)];
+        bottom.extend(horizontal);
+        bottom.push(Cell::border(BORDER_LEFT | BORDER_UP, fg));
+        buffer.push_line(bottom);
+        buffer.draw_sides(fg);
+        buffer.place(content, 2, 1);
+        buffer
+    }
+
+    fn draw_sides(&mut self, fg: Option<Color>) {
+        let width = self.width;
+        let height = self.height();
+        for line in self.lines.iter_mut().take(height - 1).skip(1) {
+            line.resize(width, Cell::blank());
+            line[0] = Cell::border(BORDER_UP | BORDER_DOWN, fg);
+            line[width - 1] = Cell::border(BORDER_UP | BORDER_DOWN, fg);
+        }
+    }
+
+    // makes a box taller by growing its sides, so that boxes next to each other line up
+    fn stretch(&mut self, height: usize) {
+        if !self.boxed || self.height() >= height {
+            return;
+        }
+        let fg = self.lines[0][0].fg;
+        let bottom = self.lines.pop().unwrap();
+        while self.lines.len() < height - 1 {
+            self.lines.push(vec![]);
+        }
+        self.lines.push(bottom);
+        self.draw_sides(fg);
+    }
+
+    // a grid of pre-aligned cells, the first line being a bold header when `header` is set
+    pub fn table(lines: Vec<Vec<String>>, header: bool, fg: Option<Color>) -> CellBuffer {
+        let widths: Vec<usize> = lines.first()
+            .map(|line| line.iter().map(|cell| measure::columns(cell) + 2).collect())
+            .unwrap_or_default();
+        let separator = |joint: u8| {
+            let mut cells = vec![];
+            for (k, width) in widths.iter().enumerate() {
+                let left = if k == 0 { 0 } else { BORDER_LEFT };
+                cells.push(Cell::border(joint | left | BORDER_RIGHT, fg));
+                cells.extend(vec![Cell::border(BORDER_LEFT | BORDER_RIGHT, fg); *width]);
+            }
+            cells.push(Cell::border(joint | BORDER_LEFT, fg));
+            cells
+        };
+        let mut buffer = CellBuffer::new(widths.iter().sum::<usize>() + widths.len() + 1);
+        buffer.boxed = true;
+        buffer.push_line(separator(BORDER_DOWN));
+        for (y, line) in lines.iter().enumerate() {
+            let bold = header && y == 0;
+            let mut cells = vec![];
+            for cell in line {
+                cells.push(Cell::border(BORDER_UP | BORDER_DOWN, fg));
+                cells.push(Cell::blank());
+                cells.extend(text_cells(cell, &Cell { bold, ..Cell::blank() }));
+                cells.push(Cell::blank());
+            }
+            cells.push(Cell::border(BORDER_UP | BORDER_DOWN, fg));
+            buffer.push_line(cells);
+            if bold && lines.len() > 1 {
+                buffer.push_line(separator(BORDER_UP | BORDER_DOWN));
+            }
+        }
+        buffer.push_line(separator(BORDER_UP));
+        buffer
+    }
+
+    // lays columns out side by side, each one in a cell `column_width` wide;
+    // when every column is a box, boxes are packed so that they share borders
+    pub fn merge_columns(columns: Vec<CellBuffer>, column_width: usize, width: usize) -> CellBuffer {
+        if !columns.is_empty() && columns.iter().all(|column| column.boxed) {
+            let height = columns.iter().map(|column| column.height()).max().unwrap_or(0);
+            let table_width = columns.iter().map(|column| column.width - 1).sum::<usize>() + 1;
+            let mut buffer = CellBuffer::new(table_width.min(width));
+            buffer.boxed = table_width <= width;
+            let mut x = 0;
+            for mut column in columns {
+                column.stretch(height);
+                let column_width = column.width;
+                buffer.place(column, x, 0);
+                x += column_width - 1;
+            }
+            return buffer;
+        }
+        let mut buffer = CellBuffer::new(width);
+        for (k, column) in columns.into_iter().enumerate() {
+            let x = k * column_width + column_width.saturating_sub(column.width) / 2;
+            buffer.place(column, x, 0);
+        }
+        buffer
+    }
+}
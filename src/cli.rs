@@ -0,0 +1,154 @@
+use crate::bema::Bema;
+use crate::error::{Error, Result};
+use crate::runner::Runner;
+use crate::hovercraft_runner::HovercraftRunner;
+use crate::terminal_runner::TerminalRunner;
+use crate::gui_runner::GuiRunner;
+use crate::html_runner::HtmlRunner;
+use crate::pdf_runner::PdfRunner;
+use crate::theme::Theme;
+use crate::graphics::Protocol;
+use std::path::PathBuf;
+
+const USAGE: &str = "usage: [MODE] [OPTIONS]
+
+modes:
+  terminal     present in the terminal (default)
+  presenter    present in the terminal with notes, next slide and a clock
+  gui          present in a window
+  hovercraft   print the deck as hovercraft reStructuredText
+  html         export a standalone html file
+  pdf          export a pdf file
+
+options:
+  --start <n|title>   start at slide number n, or at the slide with that title
+  --output <dir>      directory exported files are written to
+  --theme <name>      color theme: dark, light, solarized-dark or solarized-light
+  --graphics <name>   terminal image protocol: kitty, iterm, sixel or blocks,
+                      detected by default, also set by the BEMA_GRAPHICS variable
+  -h, --help          print this help";
+
+enum Mode {
+    Terminal,
+    Presenter,
+    Gui,
+    Hovercraft,
+    Html,
+    Pdf,
+    Help,
+}
+
+enum Start {
+    Number(usize),
+    Title(String),
+}
+
+struct Options {
+    mode: Mode,
+    start: Option<Start>,
+    output: Option<PathBuf>,
+    theme: Option<Theme>,
+    graphics: Option<Protocol>,
+}
+
+fn usage_error(message: String) -> Error {
+    Error::Usage(format!("{}\n\n{}", message, USAGE))
+}
+
+fn parse_mode(arg: &str) -> Result<Mode> {
+    match arg {
+        "terminal" => Ok(Mode::Terminal),
+        "presenter" => Ok(Mode::Presenter),
+        "gui" => Ok(Mode::Gui),
+        "hovercraft" => Ok(Mode::Hovercraft),
+        "html" => Ok(Mode::Html),
+        "pdf" => Ok(Mode::Pdf),
+        _ => Err(usage_error(format!("unknown mode `{}`", arg))),
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options> {
+    let mut options = Options { mode: Mode::Terminal, start: None, output: None, theme: None, graphics: None };
+    let mut mode = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // options take their value either as the next argument or after an `=`
+        let (name, inline_value) = match arg.find('=') {
+            Some(k) if arg.starts_with("--") => (&arg[..k], Some(arg[k + 1..].to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || inline_value.clone()
+            .or_else(|| args.next().cloned())
+            .ok_or_else(|| usage_error(format!("missing value for `{}`", name)));
+        match name {
+            "-h" | "--help" => options.mode = Mode::Help,
+            "--start" => {
+                let value = value()?;
+                options.start = Some(match value.parse() {
+                    Ok(n) => Start::Number(n),
+                    Err(_) => Start::Title(value),
+                });
+            },
+            "--output" => options.output = Some(PathBuf::from(value()?)),
+            "--theme" => {
+                let value = value()?;
+                options.theme = Some(Theme::by_name(&value).ok_or_else(|| usage_error(format!("unknown theme `{}`", value)))?);
+            },
+            "--graphics" => {
+                let value = value()?;
+                options.graphics = Some(Protocol::by_name(&value).ok_or_else(|| usage_error(format!("unknown graphics protocol `{}`", value)))?);
+            },
+            _ if name.starts_with('-') => return Err(usage_error(format!("unknown option `{}`", name))),
+            _ if mode.is_none() => mode = Some(parse_mode(name)?),
+            _ => return Err(usage_error(format!("unexpected argument `{}`", name))),
+        }
+    }
+    if let Some(mode) = mode {
+        if !matches!(options.mode, Mode::Help) {
+            options.mode = mode;
+        }
+    }
+    Ok(options)
+}
+
+// index of the slide to start at, from a 1-based number or a title
+fn start_index(bema: &Bema, start: &Option<Start>) -> Result<usize> {
+    match start {
+        None => Ok(0),
+        Some(Start::Number(n)) if *n >= 1 && *n <= bema.slides.len() => Ok(n - 1),
+        Some(Start::Number(n)) => Err(usage_error(format!("no slide {}, the deck has {} slides", n, bema.slides.len()))),
+        Some(Start::Title(title)) => bema.slides.iter()
+            .position(|slide| slide.title.eq_ignore_ascii_case(title))
+            .ok_or_else(|| usage_error(format!("no slide titled `{}`", title))),
+    }
+}
+
+// runs a deck according to command line arguments, not including the program name
+pub fn run(bema: &Bema, args: &[String]) -> Result<()> {
+    let options = parse_options(args)?;
+    if let Mode::Help = options.mode {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    // every runner starts at a slide
+    if bema.slides.is_empty() {
+        return Err(Error::Deck("deck has no slides".to_string()));
+    }
+    let mut bema = bema.clone();
+    if let Some(theme) = options.theme {
+        bema = bema.theme(theme);
+    }
+    let start = start_index(&bema, &options.start)?;
+    let output = options.output;
+    let graphics = options.graphics;
+    match options.mode {
+        Mode::Help => {},
+        Mode::Terminal => TerminalRunner { presenter: false, start, graphics }.run(&bema)?,
+        Mode::Presenter => TerminalRunner { presenter: true, start, graphics }.run(&bema)?,
+        Mode::Gui => GuiRunner { start }.run(&bema)?,
+        Mode::Hovercraft => HovercraftRunner { output }.run(&bema)?,
+        Mode::Html => HtmlRunner { output, start }.run(&bema)?,
+        Mode::Pdf => PdfRunner { output }.run(&bema)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+use std::io;
+
+pub enum Error {
+    // an image that could not be decoded or encoded
+    Image(image::ImageError),
+    // a grammar or a code theme that could not be loaded
+    Syntax(String),
+    Io(io::Error),
+    Terminal(crossterm::ErrorKind),
+    Pdf(printpdf::Error),
+    // invalid command line arguments, the message includes the usage
+    Usage(String),
+    // a deck that cannot be presented, such as one without slides
+    Deck(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Image(e) => write!(f, "image error: {}", e),
+            Error::Syntax(e) => write!(f, "syntax error: {}", e),
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::Terminal(e) => write!(f, "terminal error: {}", e),
+            Error::Pdf(e) => write!(f, "pdf error: {}", e),
+            Error::Usage(e) => write!(f, "{}", e),
+            Error::Deck(e) => write!(f, "deck error: {}", e),
+        }
+    }
+}
+
+// a `main` returning an error prints it with Debug, the usage message has to stay readable
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Image(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Terminal(e) => Some(e),
+            Error::Pdf(e) => Some(e),
+            Error::Syntax(_) | Error::Usage(_) | Error::Deck(_) => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Error {
+        Error::Image(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<crossterm::ErrorKind> for Error {
+    fn from(e: crossterm::ErrorKind) -> Error {
+        Error::Terminal(e)
+    }
+}
+
+impl From<printpdf::Error> for Error {
+    fn from(e: printpdf::Error) -> Error {
+        Error::Pdf(e)
+    }
+}
+
+impl From<syntect::LoadingError> for Error {
+    fn from(e: syntect::LoadingError) -> Error {
+        Error::Syntax(e.to_string())
+    }
+}
+
+impl From<syntect::parsing::ParseSyntaxError> for Error {
+    fn from(e: syntect::parsing::ParseSyntaxError) -> Error {
+        Error::Syntax(e.to_string())
+    }
+}
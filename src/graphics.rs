@@ -0,0 +1,244 @@
+use crate::error::Result;
+use std::env;
+use std::io::{stdout, Write};
+use std::time::{Duration, Instant};
+use crossterm::terminal::{enable_raw_mode, disable_raw_mode};
+
+// how images are drawn in the terminal
+#[derive(Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Kitty,
+    Iterm,
+    Sixel,
+    // unicode half blocks, two pixels per cell, understood by every terminal
+    Blocks,
+}
+
+impl Protocol {
+    pub fn by_name(name: &str) -> Option<Protocol> {
+        match name {
+            "kitty" => Some(Protocol::Kitty),
+            "iterm" => Some(Protocol::Iterm),
+            "sixel" => Some(Protocol::Sixel),
+            "blocks" => Some(Protocol::Blocks),
+            _ => None,
+        }
+    }
+}
+
+// a protocol chosen on the command line wins over `BEMA_GRAPHICS`, which wins over asking the terminal
+pub fn detect(choice: Option<Protocol>) -> Protocol {
+    choice
+        .or_else(|| env::var("BEMA_GRAPHICS").ok().and_then(|name| Protocol::by_name(&name)))
+        .or_else(|| match env::var("TERM_PROGRAM").as_deref() {
+            // iterm2 does not answer queries about its image protocol
+            Ok("iTerm.app") | Ok("WezTerm") => Some(Protocol::Iterm),
+            _ => None,
+        })
+        .unwrap_or_else(query)
+}
+
+// sends a kitty graphics query followed by a device attributes request: every terminal answers the
+// latter, kitty answers the former first, and terminals with sixel support list attribute 4
+#[cfg(unix)]
+fn query() -> Protocol {
+    if enable_raw_mode().is_err() {
+        return Protocol::Blocks;
+    }
+    let mut out = stdout();
+    let _ = out.write_all(b"\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[c");
+    let _ = out.flush();
+    let reply = read_reply();
+    let _ = disable_raw_mode();
+    parse_reply(&String::from_utf8_lossy(&reply))
+}
+
+#[cfg(not(unix))]
+fn query() -> Protocol {
+    Protocol::Blocks
+}
+
+// what the terminal writes back, up to the end of the device attributes or for half a second at most;
+// stdin is read directly because crossterm would turn the replies into key presses
+#[cfg(unix)]
+fn read_reply() -> Vec<u8> {
+    let start = Instant::now();
+    let mut reply = vec![];
+    while let Some(left) = Duration::from_millis(500).checked_sub(start.elapsed()) {
+        let mut fd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+        let mut byte = 0u8;
+        if unsafe { libc::poll(&mut fd, 1, left.as_millis() as libc::c_int) } <= 0
+            || unsafe { libc::read(libc::STDIN_FILENO, &mut byte as *mut u8 as *mut libc::c_void, 1) } != 1 {
+            break;
+        }
+        reply.push(byte);
+        if byte == b'c' && String::from_utf8_lossy(&reply).contains("\x1b[?") {
+            break;
+        }
+    }
+    reply
+}
+
+fn parse_reply(reply: &str) -> Protocol {
+    if reply.contains("_Gi=31;OK") {
+        return Protocol::Kitty;
+    }
+    let attributes = reply.rfind("\x1b[?").map(|k| &reply[k + 3..]).unwrap_or("");
+    if attributes.trim_end_matches('c').split(';').any(|attribute| attribute == "4") {
+        Protocol::Sixel
+    } else {
+        Protocol::Blocks
+    }
+}
+
+// size in pixels of a terminal cell, 8 by 16 when the terminal does not tell
+#[cfg(unix)]
+pub fn cell_size() -> (usize, usize) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let known = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+        && size.ws_col > 0 && size.ws_row > 0 && size.ws_xpixel > 0 && size.ws_ypixel > 0;
+    if known {
+        ((size.ws_xpixel / size.ws_col) as usize, (size.ws_ypixel / size.ws_row) as usize)
+    } else {
+        (8, 16)
+    }
+}
+
+#[cfg(not(unix))]
+pub fn cell_size() -> (usize, usize) {
+    (8, 16)
+}
+
+// the escape sequence drawing a png over `columns` x `rows` cells from the cursor
+pub fn encode(protocol: Protocol, png: &[u8], columns: usize, rows: usize) -> Result<Vec<u8>> {
+    match protocol {
+        Protocol::Kitty => Ok(kitty(png, columns, rows)),
+        Protocol::Iterm => Ok(iterm(png, columns, rows)),
+        Protocol::Sixel => sixel(png, columns, rows),
+        Protocol::Blocks => Ok(vec![]),
+    }
+}
+
+// delete every image, images are otherwise kept over the text when the cells change
+pub fn kitty_clear() -> &'static [u8] {
+    b"\x1b_Ga=d,q=2\x1b\\"
+}
+
+// the png is sent in base64 chunks of at most 4096 bytes, the cursor is left where it was
+fn kitty(png: &[u8], columns: usize, rows: usize) -> Vec<u8> {
+    let data = base64::encode(png);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    let mut out = vec![];
+    for (k, chunk) in chunks.iter().enumerate() {
+        let more = if k + 1 < chunks.len() { 1 } else { 0 };
+        let control = if k == 0 {
+            format!("a=T,f=100,q=2,C=1,c={},r={},m={}", columns, rows, more)
+        } else {
+            format!("m={}", more)
+        };
+        out.extend(format!("\x1b_G{};", control).into_bytes());
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(b"\x1b\\");
+    }
+    out
+}
+
+fn iterm(png: &[u8], columns: usize, rows: usize) -> Vec<u8> {
+    format!("\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07",
+        png.len(), columns, rows, base64::encode(png)).into_bytes()
+}
+
+fn push_sixels(out: &mut String, bits: u8, count: usize) {
+    let symbol = (63 + bits) as char;
+    if count > 3 {
+        out.push_str(&format!("!{}{}", count, symbol));
+    } else {
+        out.push_str(&symbol.to_string().repeat(count));
+    }
+}
+
+// pixels are quantized to a 6x6x6 colour cube; each band of six pixel rows is drawn once per colour
+// it uses, runs of identical columns being repeated, transparent pixels leaving the background
+fn sixel(png: &[u8], columns: usize, rows: usize) -> Result<Vec<u8>> {
+    let (cell_width, cell_height) = cell_size();
+    let img = image::load_from_memory(png)?
+        .resize((columns * cell_width) as u32, (rows * cell_height) as u32, image::imageops::FilterType::Triangle)
+        .to_rgba8();
+    Ok(sixel_pixels(&img))
+}
+
+fn sixel_pixels(img: &image::RgbaImage) -> Vec<u8> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let level = |c: u8| (c as usize * 5 + 127) / 255;
+    let pixels: Vec<Option<usize>> = img.pixels()
+        .map(|p| if p[3] < 128 { None } else { Some(level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])) })
+        .collect();
+
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for k in 0..216 {
+        out.push_str(&format!("#{};2;{};{};{}", k, k / 36 * 20, k / 6 % 6 * 20, k % 6 * 20));
+    }
+    for top in (0..height).step_by(6) {
+        let band = top..(top + 6).min(height);
+        let mut colors: Vec<usize> = band.clone().flat_map(|y| pixels[y * width..(y + 1) * width].iter().flatten().cloned()).collect();
+        colors.sort_unstable();
+        colors.dedup();
+        for color in colors {
+            out.push_str(&format!("#{}", color));
+            let mut run = (0, 0);
+            for x in 0..width {
+                let bits = band.clone().enumerate()
+                    .filter(|(_, y)| pixels[y * width + x] == Some(color))
+                    .fold(0u8, |bits, (dy, _)| bits | 1 << dy);
+                if bits != run.0 && run.1 > 0 {
+                    push_sixels(&mut out, run.0, run.1);
+                    run.1 = 0;
+                }
+                run = (bits, run.1 + 1);
+            }
+            // empty sixels at the end of a line draw nothing
+            if run.0 != 0 {
+                push_sixels(&mut out, run.0, run.1);
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies() {
+        assert!(parse_reply("\x1b_Gi=31;OK\x1b\\\x1b[?62;4;22c") == Protocol::Kitty);
+        assert!(parse_reply("\x1b[?62;4;22c") == Protocol::Sixel);
+        assert!(parse_reply("\x1b[?64;22c") == Protocol::Blocks);
+        // the attribute list is split, 42 is not 4
+        assert!(parse_reply("\x1b[?1;42c") == Protocol::Blocks);
+        assert!(parse_reply("") == Protocol::Blocks);
+    }
+
+    fn encoded(img: &image::RgbaImage) -> String {
+        let out = String::from_utf8(sixel_pixels(img)).unwrap();
+        // the palette is the same for every image
+        out[out.rfind("#215;2;100;100;100").unwrap() + 18..].to_string()
+    }
+
+    #[test]
+    fn sixels() {
+        let red = image::Rgba([255, 0, 0, 255]);
+        let clear = image::Rgba([0, 0, 0, 0]);
+        assert_eq!(encoded(&image::RgbaImage::from_pixel(2, 1, red)), "#180@@$-\x1b\\");
+        // long runs are repeated, bits are the rows of a six pixel band
+        assert_eq!(encoded(&image::RgbaImage::from_pixel(5, 2, red)), "#180!5B$-\x1b\\");
+        // transparent pixels are not drawn, nor empty sixels at the end of a line
+        let mut img = image::RgbaImage::from_pixel(3, 1, clear);
+        img.put_pixel(0, 0, red);
+        assert_eq!(encoded(&img), "#180@$-\x1b\\");
+        assert_eq!(encoded(&image::RgbaImage::from_pixel(1, 7, clear)), "--\x1b\\");
+    }
+}
@@ -1,15 +1,17 @@
-use crate::runner::{Runner, get_justify, fit_image_bytes};
-use crate::bema::{Bema, SlideItem, Slide};
+use crate::runner::{Runner, chrome, has_footer, decode_image, image_layout, scale_image_size, source_modified, reload_if_changed, step_count, reveal, Highlighting, load_highlighting};
+use crate::bema::{Bema, SlideItem, Slide, Layout, Align, VAlign, ImageSize, CodeOverflow, Metadata, DEFAULT_HEADER};
+use crate::markup::{self, Span};
+use crate::measure;
+use crate::theme::Theme;
+use crate::navigation::{Navigator, Navigation, Key};
 use indoc::indoc;
+use syntect::highlighting::Style;
 
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::time::{SystemTime, Duration};
-use syntect::easy::HighlightLines;
-use syntect::parsing::SyntaxSet;
-use syntect::highlighting::{ThemeSet, Style};
-use syntect::util::LinesWithEndings;
 
-use crossterm::Result;
+use crate::error::Result;
 use macroquad::prelude::*;
 use miniquad::{BlendState, BlendValue, BlendFactor, Equation};
 
@@ -18,52 +20,119 @@ fn get_transition_duration() -> u128 {
 }
 
 pub struct GuiRunner {
+    pub start: usize,
 }
 
 
-fn get_justify_px(font_size: u16, texts: Vec<&String>, total_width: f32) -> f32 {
-    let font_width = font_size / 2;
-    (font_width as usize * get_justify((total_width/ font_width as f32) as usize, texts).unwrap_or(0)) as f32
+fn color((r, g, b): (u8, u8, u8)) -> Color {
+    Color::from_rgba(r, g, b, 255)
 }
 
-fn main_draw_texture(textures: &mut HashMap<(i32, usize),Texture2D>, bytes: &[u8], width: &Option<usize>, extension: &String, pos: usize, i: i32, dx: f32, y: &mut f32, total_width: f32) {
-    match textures.get(&(i, pos)) {
-        Some(_) => {},
-        None => {
-                let bytes = fit_image_bytes(bytes, width, extension);
-                let texture = Texture2D::from_file_with_format(&bytes[..], None);
-                textures.insert((i, pos), texture);
-            }
-    };
-    let texture = *textures.get(&(i, pos)).unwrap();
-    let w = total_width;
-    let x = if w < texture.width() {
-        0.0
-    } else {
-        (w - texture.width()) / 2.0
+fn text_width(font: Font, font_size: u16, text: &str) -> f32 {
+    measure_text(text, Some(font), font_size, 1.0).width
+}
+
+fn justify_px<T: AsRef<str>>(align: Align, font: Font, font_size: u16, lines: &[T], total_width: f32) -> f32 {
+    measure::justify(align, total_width as f64, lines, |line| text_width(font, font_size, line) as f64) as f32
+}
+
+fn texture(textures: &mut HashMap<(i32, usize),Texture2D>, bytes: &[u8], extension: &str, pos: usize, i: i32) -> Option<Texture2D> {
+    match textures.entry((i, pos)) {
+        Entry::Occupied(entry) => Some(*entry.get()),
+        Entry::Vacant(entry) => {
+            // images are checked before the window opens, one broken by a reload is skipped
+            let img = decode_image(bytes, extension).ok()?.to_rgba8();
+            Some(*entry.insert(Texture2D::from_rgba8(img.width() as u16, img.height() as u16, &img.into_raw())))
+        },
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn main_draw_texture(textures: &mut HashMap<(i32, usize),Texture2D>, bytes: &[u8], size: &ImageSize, extension: &str, pos: usize, i: i32, align: Align, dx: f32, y: &mut f32, total_width: f32, total_height: f32) {
+    let texture = match texture(textures, bytes, extension, pos, i) {
+        Some(texture) => texture,
+        None => return,
     };
-    draw_texture(texture, x + dx, *y, WHITE);
-    *y += texture.width();
+    let layout = image_layout(&scale_image_size(size, screen_width() as f64), (texture.width() as f64, texture.height() as f64), (total_width as f64, total_height as f64));
+    let (w, h) = (layout.width as f32, layout.height as f32);
+    let (cx, cy, cw, ch) = layout.crop;
+    draw_texture_ex(texture, measure::offset(align, (total_width - w) as f64) as f32 + dx, *y, WHITE, DrawTextureParams {
+        dest_size: Some(vec2(w, h)),
+        source: Some(Rect::new(cx as f32, cy as f32, cw as f32, ch as f32)),
+        ..Default::default()
+    });
+    *y += h;
+}
+
+// errors cannot leave the window loop, so images are decoded once up front
+fn check_images(items: &[SlideItem]) -> Result<()> {
+    for item in items {
+        match item {
+            SlideItem::Image { image, extension, .. } => { decode_image(image, extension)?; },
+            SlideItem::Cols { items } | SlideItem::Rows { items } | SlideItem::Framed { items } | SlideItem::Aligned { items, .. } => check_images(items)?,
+            _ => {},
+        }
+    }
+    Ok(())
 }
 
-fn main_capture_input(bema: &Bema, i: &mut i32, scale: &mut f32, antibounce: &mut SystemTime, transition: &mut SystemTime, transition_direction: &mut f32, help: &mut bool, decoration: &mut bool, white_mode: &mut bool) {
+#[allow(clippy::too_many_arguments)]
+fn main_capture_input(bema: &Bema, navigator: &mut Navigator, i: &mut i32, step: &mut usize, scale: &mut f32, antibounce: &mut SystemTime, transition: &mut SystemTime, transition_direction: &mut f32, help: &mut bool, decoration: &mut bool, alternate_theme: &mut bool, overview: &mut Option<usize>) {
     let mut changed = false;
 
+    // typed keys go to the navigator first, the bindings below only see the ones it ignores
+    let mut keys = vec![];
+    while let Some(c) = get_char_pressed() {
+        if !c.is_control() {
+            keys.push(Key::Char(c));
+        }
+    }
+    if is_key_pressed(miniquad::KeyCode::Enter) { keys.push(Key::Enter); }
+    if is_key_pressed(miniquad::KeyCode::Escape) { keys.push(Key::Escape); }
+    if is_key_pressed(miniquad::KeyCode::Backspace) { keys.push(Key::Backspace); }
+    let mut consumed = false;
+    for key in keys {
+        match navigator.key(bema, *i as usize, key) {
+            Navigation::Goto(k) => {
+                *i = k as i32;
+                *step = 0;
+                *transition_direction = 0.0;
+                consumed = true;
+            },
+            Navigation::Consumed => consumed = true,
+            Navigation::Ignored => {},
+        }
+    }
+    if consumed || navigator.is_active() {
+        *antibounce = SystemTime::now();
+        return;
+    }
+
     if antibounce.elapsed().unwrap_or(Duration::from_millis(0)).as_millis() >= get_transition_duration() {
         if is_key_down(miniquad::KeyCode::Right) || is_key_down(miniquad::KeyCode::Down) || is_key_down(miniquad::KeyCode::L) || is_key_down(miniquad::KeyCode::J) || is_key_down(miniquad::KeyCode::N) || is_key_down(miniquad::KeyCode::Space) || is_mouse_button_down(miniquad::MouseButton::Left) {
-            *i += 1;
-            *transition_direction = -1.0;
-            changed = true;
+            if *step < step_count(&bema.slides[*i as usize].items) {
+                *step += 1;
+            } else {
+                *i += 1;
+                *step = 0;
+                *transition_direction = -1.0;
+                changed = true;
+            }
         }
         if is_key_down(miniquad::KeyCode::Left) || is_key_down(miniquad::KeyCode::Up) || is_key_down(miniquad::KeyCode::H) || is_key_down(miniquad::KeyCode::K) || is_key_down(miniquad::KeyCode::P) || is_mouse_button_down(miniquad::MouseButton::Right) {
-            *i -= 1;
-            *transition_direction = 1.0;
-            changed = true;
+            if *step > 0 {
+                *step -= 1;
+            } else {
+                *i -= 1;
+                *step = usize::MAX;
+                *transition_direction = 1.0;
+                changed = true;
+            }
         }
         if is_key_down(miniquad::KeyCode::Q) {
             std::process::exit(0);
         }
-        if is_key_down(miniquad::KeyCode::M) {
+        if is_key_down(miniquad::KeyCode::Equal) || is_key_down(miniquad::KeyCode::KpAdd) {
             *scale *= 1.1;
         }
         if is_key_down(miniquad::KeyCode::R) {
@@ -75,8 +144,12 @@ fn main_capture_input(bema: &Bema, i: &mut i32, scale: &mut f32, antibounce: &mu
         if is_key_down(miniquad::KeyCode::D) {
             *decoration = !*decoration;
         }
+        if is_key_down(miniquad::KeyCode::O) {
+            *overview = Some(*i as usize);
+            *help = false;
+        }
         if is_key_down(miniquad::KeyCode::C) {
-            *white_mode = !*white_mode;
+            *alternate_theme = !*alternate_theme;
         }
         if is_key_down(miniquad::KeyCode::S) {
             let png_path = format!("bema_slide_{}.png", *i);
@@ -85,6 +158,7 @@ fn main_capture_input(bema: &Bema, i: &mut i32, scale: &mut f32, antibounce: &mu
         }
         if is_key_down(miniquad::KeyCode::G) {
             *i = 0;
+            *step = 0;
         }
         if *i >= bema.slides.len() as i32 {
             *i = 0;
@@ -92,6 +166,7 @@ fn main_capture_input(bema: &Bema, i: &mut i32, scale: &mut f32, antibounce: &mu
         else if *i < 0 {
             *i = bema.slides.len() as i32 - 1;
         }
+        *step = (*step).min(step_count(&bema.slides[*i as usize].items));
         *antibounce = SystemTime::now();
         if changed {
             *transition = SystemTime::now();
@@ -100,111 +175,338 @@ fn main_capture_input(bema: &Bema, i: &mut i32, scale: &mut f32, antibounce: &mu
     }
 }
 
+// slides per row and per column of the overview grid
+fn overview_columns(bema: &Bema) -> usize {
+    ((bema.slides.len() as f32).sqrt().ceil() as usize).max(1)
+}
+
+// arrows or H, J, K, L move the selection, Enter or a click goes to a slide, Escape or O closes the overview
+fn overview_capture_input(bema: &Bema, overview: &mut Option<usize>, i: &mut i32, step: &mut usize, antibounce: &mut SystemTime, transition_direction: &mut f32) {
+    while get_char_pressed().is_some() {}
+    let selected = match overview.as_mut() {
+        Some(selected) => selected,
+        None => return,
+    };
+    let columns = overview_columns(bema);
+    let last = bema.slides.len() - 1;
+    if is_key_pressed(miniquad::KeyCode::Right) || is_key_pressed(miniquad::KeyCode::L) {
+        *selected = (*selected + 1).min(last);
+    }
+    if is_key_pressed(miniquad::KeyCode::Left) || is_key_pressed(miniquad::KeyCode::H) {
+        *selected = selected.saturating_sub(1);
+    }
+    if is_key_pressed(miniquad::KeyCode::Down) || is_key_pressed(miniquad::KeyCode::J) {
+        *selected = (*selected + columns).min(last);
+    }
+    if is_key_pressed(miniquad::KeyCode::Up) || is_key_pressed(miniquad::KeyCode::K) {
+        *selected = selected.saturating_sub(columns);
+    }
+    let mut chosen = None;
+    if is_key_pressed(miniquad::KeyCode::Enter) {
+        chosen = Some(*selected);
+    }
+    if is_mouse_button_pressed(miniquad::MouseButton::Left) {
+        let (x, y) = mouse_position();
+        let column = (x / (screen_width() / columns as f32)) as usize;
+        let row = (y / (screen_height() / columns as f32)) as usize;
+        let k = row * columns + column.min(columns - 1);
+        if k <= last {
+            chosen = Some(k);
+        }
+    }
+    if let Some(k) = chosen {
+        *i = k as i32;
+        *step = 0;
+        *transition_direction = 0.0;
+        *overview = None;
+    } else if is_key_pressed(miniquad::KeyCode::Escape) || is_key_pressed(miniquad::KeyCode::O) {
+        *overview = None;
+    }
+    // the key closing the overview must not also act on the slide
+    *antibounce = SystemTime::now();
+}
+
 fn scalef(font_size: u16, scale: f32) -> u16 {
-    (font_size as f32 * scale as f32) as u16
+    (font_size as f32 * scale) as u16
 }
 
-fn write_text(text_size: u16, font: Font, font_color: Color, dx: f32, y: &mut f32, text: &String, total_width: f32) {
-    let splits = text.split("\n").map( |x| x.to_string()).collect::<Vec<_>>();
-    let v2: Vec<&String> = splits.iter().map(|s| s).collect::<Vec<&String>>();
-    let x = get_justify_px(text_size, v2, total_width) + dx;
-    for split in splits {
-        draw_text_ex(&split, x, *y + text_size as f32, TextParams { font_size: text_size, font,
-            color: font_color,
-            ..Default::default()
-        });
+fn text_lines(text_size: u16, font: Font, text: &str, total_width: f32) -> Vec<Vec<Span>> {
+    markup::parse(text).iter()
+        .flat_map(|spans| markup::wrap(spans, total_width as f64, |t| text_width(font, text_size, t) as f64))
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_text(text_size: u16, font: Font, theme: &Theme, align: Align, dx: f32, y: &mut f32, text: &str, total_width: f32) {
+    let lines = text_lines(text_size, font, text, total_width);
+    let plains = lines.iter().map(|spans| markup::plain(spans)).collect::<Vec<_>>();
+    let x = justify_px(align, font, text_size, &plains, total_width) + dx;
+    for spans in lines {
+        let mut x2 = x;
+        for span in spans {
+            let style = span.style;
+            let width = text_width(font, text_size, &span.text);
+            let mut color = color(style.color.unwrap_or(if style.code { theme.accent } else { theme.text }));
+            // the font has no italic face, italic runs are drawn faded instead
+            if style.italic {
+                color.a = 0.6;
+            }
+            let params = TextParams { font_size: text_size, font, color, ..Default::default() };
+            draw_text_ex(&span.text, x2, *y + text_size as f32, params);
+            // the font has a single weight, bold is drawn twice slightly shifted
+            if style.bold {
+                draw_text_ex(&span.text, x2 + 1.5, *y + text_size as f32, params);
+            }
+            if style.underline {
+                let line_y = *y + text_size as f32 * 1.05;
+                draw_line(x2, line_y, x2 + width, line_y, 3.0, color);
+            }
+            x2 += width;
+        }
         *y += text_size as f32;
     }
 }
 
-fn write_code(text_size: u16, font: Font, dx: f32, y: &mut f32, extension: &String, source: &String, total_width: f32) {
-    let ps = SyntaxSet::load_defaults_newlines();
-    let ts = ThemeSet::load_defaults();
-
-    let syntax = ps.find_syntax_by_extension(extension).unwrap();
-    let mut h = HighlightLines::new(syntax, &ts.themes["base16-ocean.dark"]);
-    let splits = source.split("\n").map( |x| x.to_string()).collect::<Vec<_>>();
-    let v2: Vec<&String> = splits.iter().map(|s| s).collect::<Vec<&String>>();
-    let x = get_justify_px(text_size, v2, total_width) + dx;
-    for line in LinesWithEndings::from(source) {
-        let ranges: Vec<(Style, &str)> = h.highlight(line, &ps);
+#[allow(clippy::too_many_arguments)]
+fn code_lines(text_size: u16, font: Font, theme: &Theme, highlighting: &Highlighting, overflow: CodeOverflow, extension: &str, source: &str, total_width: f32) -> Vec<Vec<(Style, String)>> {
+    highlighting.highlight(extension, source, theme).iter()
+        .flat_map(|ranges| measure::fit_code(ranges, total_width as f64, overflow, |t| text_width(font, text_size, t) as f64))
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_code(text_size: u16, font: Font, theme: &Theme, highlighting: &Highlighting, overflow: CodeOverflow, align: Align, dx: f32, y: &mut f32, extension: &str, source: &str, total_width: f32) {
+    let lines = code_lines(text_size, font, theme, highlighting, overflow, extension, source, total_width);
+    let plains = lines.iter().map(|ranges| ranges.iter().map(|range| range.1.as_str()).collect::<String>()).collect::<Vec<_>>();
+    let x = justify_px(align, font, text_size, &plains, total_width) + dx;
+    for ranges in lines {
         let mut dx = 0.0;
         for range in ranges {
             let c = range.0.foreground;
-            draw_text_ex(range.1, (x + (dx * (text_size as f32 / 2.0))) as f32, *y + text_size as f32, TextParams { font_size: text_size, font,
+            draw_text_ex(&range.1, x + dx, *y + text_size as f32, TextParams { font_size: text_size, font,
             color: macroquad::color::Color::new(c.r as f32 / 255.0, c.g as f32 / 255.0, c.b as f32 / 255.0, c.a as f32 / 255.0),
             ..Default::default()
             });
-            dx += range.1.len() as f32;
+            dx += text_width(font, text_size, &range.1);
+        }
+        *y += text_size as f32;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_table(text_size: u16, font: Font, theme: &Theme, table_align: Align, dx: f32, y: &mut f32, header: &[String], rows: &[Vec<String>], align: &[Align], total_width: f32) {
+    let font_color = color(theme.text);
+    let frame_color = color(theme.frame);
+    let padding = text_width(font, text_size, " ");
+    let mut lines = vec![];
+    if !header.is_empty() {
+        lines.push(header);
+    }
+    lines.extend(rows.iter().map(|row| row.as_slice()));
+    let columns = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    let widths: Vec<f32> = (0..columns).map(|k| {
+        lines.iter().filter_map(|line| line.get(k)).map(|cell| text_width(font, text_size, cell)).fold(0.0, f32::max) + 2.0 * padding
+    }).collect();
+    let table_width = widths.iter().sum::<f32>();
+    let x0 = dx + measure::offset(table_align, (total_width - table_width) as f64) as f32;
+    let y0 = *y;
+    for (k, line) in lines.iter().enumerate() {
+        let mut x = x0;
+        for (column, width) in widths.iter().enumerate() {
+            let cell = line.get(column).map(|c| c.as_str()).unwrap_or("");
+            let room = width - 2.0 * padding - text_width(font, text_size, cell);
+            let offset = match align.get(column).unwrap_or(&Align::Left) {
+                Align::Left => 0.0,
+                Align::Center => room / 2.0,
+                Align::Right => room,
+            };
+            draw_text_ex(cell, x + padding + offset, *y + text_size as f32, TextParams { font_size: text_size, font,
+                color: font_color,
+                ..Default::default()
+            });
+            x += width;
         }
         *y += text_size as f32;
+        let thickness = if k == 0 && !header.is_empty() { 4.0 } else { 2.0 };
+        draw_line(x0, *y, x0 + table_width, *y, thickness, frame_color);
+    }
+    draw_line(x0, y0, x0 + table_width, y0, 2.0, frame_color);
+    let mut x = x0;
+    draw_line(x, y0, x, *y, 2.0, frame_color);
+    for width in widths {
+        x += width;
+        draw_line(x, y0, x, *y, 2.0, frame_color);
+    }
+}
+
+// the height of the slide below its index and title, that images are sized against
+fn image_area_height(scale: f32) -> f32 {
+    screen_height() - (scalef(20, scale) + 3 * scalef(80, scale)) as f32
+}
+
+// how much `draw_item` moves `y` down, without drawing anything
+#[allow(clippy::too_many_arguments)]
+fn item_height(font: Font, theme: &Theme, highlighting: &Highlighting, overflow: CodeOverflow, i: i32, pos: usize, item: &SlideItem, total_width: f32, textures: &mut HashMap<(i32, usize), Texture2D>, scale: f32) -> f32 {
+    let text_size : u16 = scalef(60, scale);
+    match item {
+        SlideItem::Image { image: bytes, extension, size } => match texture(textures, bytes, extension, pos, i) {
+            Some(texture) => {
+                let area = (total_width as f64, image_area_height(scale) as f64);
+                image_layout(&scale_image_size(size, screen_width() as f64), (texture.width() as f64, texture.height() as f64), area).height as f32
+            },
+            None => 0.0,
+        },
+        SlideItem::Code { extension, source } => {
+            code_lines(text_size, font, theme, highlighting, overflow, extension, source, total_width).len() as f32 * text_size as f32
+        },
+        SlideItem::Text { text } => text_lines(text_size, font, text, total_width).len() as f32 * text_size as f32,
+        SlideItem::Cols { items } => {
+            let w = total_width / items.len() as f32;
+            items.iter().enumerate()
+                .map(|(pos2, item2)| item_height(font, theme, highlighting, overflow, i, pos + pos2, item2, w, textures, scale))
+                .fold(0.0, f32::max)
+        },
+        SlideItem::Rows { items } | SlideItem::Aligned { items, .. } => {
+            items.iter().enumerate()
+                .map(|(k, item2)| item_height(font, theme, highlighting, overflow, i, pos + k, item2, total_width, textures, scale))
+                .sum()
+        },
+        SlideItem::Framed { items } => {
+            let padding = text_size as f32 / 2.0;
+            items.iter().enumerate()
+                .map(|(k, item2)| item_height(font, theme, highlighting, overflow, i, pos + k, item2, total_width - 2.0 * padding, textures, scale))
+                .sum::<f32>() + 2.0 * padding
+        },
+        SlideItem::Table { header, rows, .. } => {
+            (rows.len() + if header.is_empty() { 0 } else { 1 }) as f32 * text_size as f32
+        },
+        SlideItem::Pause => 0.0,
     }
 }
 
-fn draw_item(font: Font, font_color: Color, i: i32, pos: usize, item: &SlideItem, dx: f32, y: &mut f32, total_width: f32, textures: &mut HashMap<(i32, usize), Texture2D>, scale: f32) {
+#[allow(clippy::too_many_arguments)]
+fn draw_item(font: Font, theme: &Theme, highlighting: &Highlighting, overflow: CodeOverflow, align: Align, i: i32, pos: usize, item: &SlideItem, dx: f32, y: &mut f32, total_width: f32, textures: &mut HashMap<(i32, usize), Texture2D>, scale: f32) {
     let text_size : u16 = scalef(60, scale);
     match item {
-        SlideItem::Image { image: bytes, extension, width } => {
-            main_draw_texture(textures, bytes, width, &extension, pos, i, dx, y, total_width);
+        SlideItem::Image { image: bytes, extension, size } => {
+            main_draw_texture(textures, bytes, size, extension, pos, i, align, dx, y, total_width, image_area_height(scale));
         },
         SlideItem::Code { extension, source } => {
-            write_code(text_size, font, dx, y, extension, source, total_width);
+            write_code(text_size, font, theme, highlighting, overflow, align, dx, y, extension, source, total_width);
         },
         SlideItem::Text { text } => {
-            write_text(text_size, font, font_color, dx, y, text, total_width);
+            write_text(text_size, font, theme, align, dx, y, text, total_width);
         },
         SlideItem::Cols { items } => {
             let w = total_width / items.len() as f32;
             let mut ys = vec![];
             for (pos2, item2) in items.iter().enumerate() {
                 let mut y2 = *y;
-                draw_item(font, font_color, i, pos + pos2, item2, dx + w * pos2 as f32, &mut y2, w, textures, scale);
+                draw_item(font, theme, highlighting, overflow, align, i, pos + pos2, item2, dx + w * pos2 as f32, &mut y2, w, textures, scale);
                 ys.push(y2);
             }
             *y = ys.iter().cloned().fold(0.0, |a, b| { a.max(b) })
         },
         SlideItem::Rows { items } => {
             for (k, item2) in items.iter().enumerate() {
-                draw_item(font, font_color, i, pos + k, item2, dx as f32, y, total_width, textures, scale);
+                draw_item(font, theme, highlighting, overflow, align, i, pos + k, item2, dx, y, total_width, textures, scale);
+            }
+        },
+        SlideItem::Aligned { align, items } => {
+            for (k, item2) in items.iter().enumerate() {
+                draw_item(font, theme, highlighting, overflow, *align, i, pos + k, item2, dx, y, total_width, textures, scale);
             }
         },
         SlideItem::Framed { items } => {
+            // the content is kept off the frame by half a line
+            let padding = text_size as f32 / 2.0;
             let y0 = *y;
+            *y += padding;
             for (k, item2) in items.iter().enumerate() {
-                draw_item(font, font_color, i, pos + k, item2, dx as f32, y, total_width, textures, scale);
+                draw_item(font, theme, highlighting, overflow, align, i, pos + k, item2, dx + padding, y, total_width - 2.0 * padding, textures, scale);
             }
-            draw_rectangle_lines(dx, y0, total_width, *y - y0, 2.0, font_color);
+            *y += padding;
+            draw_rectangle_lines(dx, y0, total_width, *y - y0, 2.0, color(theme.frame));
         },
+        SlideItem::Table { header, rows, align: column_align } => {
+            draw_table(text_size, font, theme, align, dx, y, header, rows, column_align, total_width);
+        },
+        SlideItem::Pause => {},
     }
 }
 
-fn draw_slide(font: Font, font_color: Color, bar_color: Color, textures: &mut HashMap<(i32, usize), Texture2D>, bema: &Bema, i: i32, dx: f32, scale: f32, total_width: f32) {
+#[allow(clippy::too_many_arguments)]
+fn draw_slide(font: Font, theme: &Theme, highlighting: &Highlighting, textures: &mut HashMap<(i32, usize), Texture2D>, bema: &Bema, i: i32, step: usize, dx: f32, scale: f32, total_width: f32) {
     let title_size : u16 = scalef(80, scale);
     let index_size : u16 = scalef(20, scale);
 
     let k = if i >= (bema.slides.len() as i32) { 0 } else if i < 0 { bema.slides.len() as i32 - 1 } else { i };
     let slide = bema.slides.get(k as usize).unwrap();
+    if slide.layout == Layout::Image {
+        // without index, title or footer, images are sized against the whole window
+        let mut y = 0.0;
+        for (pos, item) in slide.items.iter().enumerate() {
+            match item {
+                SlideItem::Image { image: bytes, extension, size } => main_draw_texture(textures, bytes, size, extension, pos, i, slide.align, dx, &mut y, total_width, screen_height()),
+                _ => draw_item(font, theme, highlighting, bema.code_overflow, slide.align, i, pos, item, dx, &mut y, total_width, textures, scale),
+            }
+        }
+        return;
+    }
+    let bar_color = color(theme.progress);
+    // the lines of a title slide below its title, with their size and colour
+    let mut below_title = vec![];
+    if let Layout::Title { subtitle, byline } = &slide.layout {
+        below_title.extend(subtitle.lines().map(|line| (line, scalef(50, scale), color(theme.accent))));
+        if !byline.is_empty() {
+            below_title.push(("", scalef(36, scale), bar_color));
+        }
+        below_title.extend(byline.lines().map(|line| (line, scalef(36, scale), bar_color)));
+    }
     let mut y = index_size as f32;
     draw_rectangle(dx, 0.0, total_width * ((i as f32 + 1.0) / bema.slides.len() as f32), index_size as f32 / 10.0, bar_color); 
-    draw_text_ex(format!("{}/{}", i + 1, bema.slides.len()).as_str(), 20.0 + dx, y, TextParams { font_size: index_size, font,
+    draw_text_ex(&chrome(&bema.header, bema, k as usize), 20.0 + dx, y, TextParams { font_size: index_size, font,
     color: bar_color,
     ..Default::default()
     });
+    let footer = chrome(&bema.footer, bema, k as usize);
+    // the footer takes a line at the bottom of the screen
+    let footer_height = if footer.is_empty() { 0.0 } else { 2.0 * index_size as f32 };
+    if !footer.is_empty() {
+        draw_text_ex(&footer, justify_px(Align::Center, font, index_size, &[&footer], total_width) + dx, screen_height() - index_size as f32 / 2.0, TextParams { font_size: index_size, font,
+        color: bar_color,
+        ..Default::default()
+        });
+    }
+    if slide.valign != VAlign::Top {
+        // placed by the height of the whole slide, so that it does not move as its steps are revealed
+        let height = 3.0 * title_size as f32 + below_title.iter().map(|(_, size, _)| *size as f32).sum::<f32>() + slide.items.iter().enumerate()
+            .map(|(pos, item)| item_height(font, theme, highlighting, bema.code_overflow, i, pos, item, total_width, textures, scale))
+            .sum::<f32>();
+        y += measure::offset_vertical(slide.valign, (screen_height() - footer_height - y - height) as f64) as f32;
+    }
     y += title_size as f32;
 
-    draw_text_ex(&slide.title, get_justify_px(title_size, vec![&slide.title], total_width) + dx, y, TextParams { font_size: title_size, font,
-    color: font_color,
+    draw_text_ex(&slide.title, justify_px(slide.align, font, title_size, &[&slide.title], total_width) + dx, y, TextParams { font_size: title_size, font,
+    color: color(theme.title),
     ..Default::default()
     });
+    for (line, size, line_color) in below_title {
+        y += size as f32;
+        draw_text_ex(line, justify_px(slide.align, font, size, &[line], total_width) + dx, y, TextParams { font_size: size, font,
+        color: line_color,
+        ..Default::default()
+        });
+    }
     y += 2.0 * title_size as f32;
-    for (pos, item) in slide.items.iter().enumerate() {
-        draw_item(font, font_color, i, pos, item, dx, &mut y, total_width, textures, scale);
+    for (pos, item) in reveal(&slide.items, step).iter().enumerate() {
+        draw_item(font, theme, highlighting, bema.code_overflow, slide.align, i, pos, item, dx, &mut y, total_width, textures, scale);
     };
 }
 
 
-fn draw_help(font: Font, font_color: Color, bar_color: Color, textures: &mut HashMap<(i32, usize), Texture2D>, decoration: bool, white_mode: bool, scale: f32) {
-            draw_slide(font, font_color, bar_color, textures, &Bema { 
+fn draw_help(font: Font, theme: &Theme, highlighting: &Highlighting, textures: &mut HashMap<(i32, usize), Texture2D>, decoration: bool, alternate_theme: bool, scale: f32) {
+            draw_slide(font, theme, highlighting, textures, &Bema { 
         slides: vec![Slide { 
             title: "bema help".to_string(), 
             items: vec![
@@ -214,21 +516,58 @@ fn draw_help(font: Font, font_color: Color, bar_color: Color, textures: &mut Has
                 next slide      right, down, L, J, N
                 previous slide  left, up, H, K, P
                 exit            Q
-                scale up        M
+                scale up        +
                 scale down      R
                 screenshot      S
+                go to slide     number, then Enter or G
+                search          /, then N for the next match
+                marks           M and a letter, ' and the letter to go back
+                overview        O
            [{}]  decoration      D 
-           [{}]  white mode      C 
+           [{}]  alt. theme      C 
            [{}]  help            Escape"
-                }, if decoration { "x" } else { " " }, if white_mode { "x" } else { " " }, "x") },
+                }, if decoration { "x" } else { " " }, if alternate_theme { "x" } else { " " }, "x") },
             ],
-        }]
-    }, 0, 0.0, scale, screen_width());
+            notes: String::new(),
+            align: Align::Center,
+            valign: VAlign::Top,
+            layout: Layout::Content,
+        }],
+        source: None,
+        theme: theme.clone(),
+        alternate_theme: theme.clone(),
+        highlighting: vec![],
+        code_overflow: CodeOverflow::Overflow,
+        metadata: Metadata::default(),
+        header: DEFAULT_HEADER.to_string(),
+        footer: String::new(),
+    }, 0, 0, 0.0, scale, screen_width());
 }
 
-async  fn main_gui_runner(bema: Bema) {
-    let font = load_ttf_font_from_bytes(include_bytes!("3270 Narrow Nerd Font Complete.ttf"));
-    let mut i : i32 = 0;
+// every slide fully revealed in a grid of thumbnails, each one drawn through its own viewport
+fn draw_overview(font: Font, theme: &Theme, highlighting: &Highlighting, textures: &mut HashMap<(i32, usize), Texture2D>, bema: &Bema, selected: usize, scale: f32) {
+    let columns = overview_columns(bema);
+    let (width, height) = (screen_width() / columns as f32, screen_height() / columns as f32);
+    for k in 0..bema.slides.len() {
+        let (x, y) = ((k % columns) as f32 * width, (k / columns) as f32 * height);
+        let mut camera = Camera2D::from_display_rect(Rect::new(0.0, 0.0, screen_width(), screen_height()));
+        // viewports are counted from the bottom of the window
+        camera.viewport = Some((x as i32, (screen_height() - y - height) as i32, width as i32, height as i32));
+        set_camera(&camera);
+        draw_slide(font, theme, highlighting, textures, bema, k as i32, usize::MAX, 0.0, scale, screen_width());
+    }
+    set_default_camera();
+    let (x, y) = ((selected % columns) as f32 * width, (selected / columns) as f32 * height);
+    draw_rectangle_lines(x, y, width, height, 4.0, color(theme.accent));
+}
+
+async  fn main_gui_runner(mut bema: Bema, highlighting: Highlighting, start: usize) {
+    let font = load_ttf_font_from_bytes(include_bytes!("3270 Narrow Nerd Font Complete.ttf")).unwrap();
+    let mut i : i32 = start as i32;
+    let mut step : usize = 0;
+    let mut navigator = Navigator::new();
+    let mut modified = source_modified(&bema);
+    let mut reload_check = SystemTime::now();
     let mut antibounce = SystemTime::now(); 
     let mut transition = SystemTime::now(); 
     let mut textures = HashMap::new();
@@ -238,7 +577,8 @@ async  fn main_gui_runner(bema: Bema) {
 
     let mut help = false;
     let mut decoration = true;
-    let mut white_mode = false;
+    let mut alternate_theme = false;
+    let mut overview: Option<usize> = None;
 
     let render_target = render_target(screen_width() as u32, (screen_height() * 0.6) as u32);
     let material =
@@ -264,20 +604,23 @@ async  fn main_gui_runner(bema: Bema) {
         .unwrap();
 
 
-    let mut font_color;
-    let mut background_color;
-    let mut bar_color;
-
     loop {
-        if white_mode {
-            font_color = BLACK;
-            background_color = WHITE;
-            bar_color = LIGHTGRAY;
+        if reload_check.elapsed().unwrap_or(Duration::from_millis(0)).as_millis() >= 500 {
+            reload_check = SystemTime::now();
+            if reload_if_changed(&mut bema, &mut modified) {
+                textures.clear();
+                i = i.min(bema.slides.len() as i32 - 1);
+                step = step.min(step_count(&bema.slides[i as usize].items));
+            }
         }
-        else {
-            font_color = WHITE;
-            background_color = BLACK;
-            bar_color = DARKGRAY;
+        let theme = if alternate_theme { &bema.alternate_theme } else { &bema.theme };
+        let background_color = color(theme.background);
+        if let Some(selected) = overview {
+            clear_background(background_color);
+            draw_overview(font, theme, &highlighting, &mut textures, &bema, selected, scale);
+            overview_capture_input(&bema, &mut overview, &mut i, &mut step, &mut antibounce, &mut transition_direction);
+            next_frame().await;
+            continue;
         }
         if decoration {
             // draw to texture
@@ -292,16 +635,21 @@ async  fn main_gui_runner(bema: Bema) {
         clear_background(background_color);
 
         if help {
-            draw_help(font, font_color, bar_color, &mut textures, decoration, white_mode, scale);
+            draw_help(font, theme, &highlighting, &mut textures, decoration, alternate_theme, scale);
         }
         else {
             let dt = transition.elapsed().unwrap_or(Duration::from_millis(0)).as_millis();
             let dt = if dt > get_transition_duration() || transition_direction == 0.0 { transition_direction = 0.0; get_transition_duration() } else { dt };
             let dx = transition_direction * screen_width() * dt as f32 / get_transition_duration() as f32;
-            if transition_direction != 0.0 { draw_slide(font, font_color, bar_color, &mut textures, &bema, i - 1 + transition_direction as i32, dx - screen_width(), scale, screen_width()); }
-
-            draw_slide(font, font_color, bar_color, &mut textures, &bema, i + transition_direction as i32, dx, scale, screen_width());
-            if transition_direction != 0.0 { draw_slide(font, font_color, bar_color, &mut textures, &bema, i + 1 + transition_direction as i32, dx + screen_width(), scale, screen_width()); }
+            // slides before the current one are shown fully revealed, the ones after it not at all
+            let step_of = |k: i32| if k < i { usize::MAX } else if k > i { 0 } else { step };
+            let k = i - 1 + transition_direction as i32;
+            if transition_direction != 0.0 { draw_slide(font, theme, &highlighting, &mut textures, &bema, k, step_of(k), dx - screen_width(), scale, screen_width()); }
+
+            let k = i + transition_direction as i32;
+            draw_slide(font, theme, &highlighting, &mut textures, &bema, k, step_of(k), dx, scale, screen_width());
+            let k = i + 1 + transition_direction as i32;
+            if transition_direction != 0.0 { draw_slide(font, theme, &highlighting, &mut textures, &bema, k, step_of(k), dx + screen_width(), scale, screen_width()); }
         }
 
 
@@ -334,7 +682,16 @@ async  fn main_gui_runner(bema: Bema) {
             );
             gl_use_default_material();
         }
-        main_capture_input(&bema, &mut i, &mut scale, &mut antibounce, &mut transition, &mut transition_direction, &mut help, &mut decoration, &mut white_mode); 
+        if let Some(status) = navigator.status() {
+            let status_size = scalef(40, scale);
+            // above the footer line, as high as the one `draw_slide` keeps
+            let footer_height = if has_footer(&bema, i as usize) { 2.0 * scalef(20, scale) as f32 } else { 0.0 };
+            draw_text_ex(&status, 20.0, screen_height() - footer_height - status_size as f32 / 2.0, TextParams { font_size: status_size, font,
+                color: color(theme.accent),
+                ..Default::default()
+            });
+        }
+        main_capture_input(&bema, &mut navigator, &mut i, &mut step, &mut scale, &mut antibounce, &mut transition, &mut transition_direction, &mut help, &mut decoration, &mut alternate_theme, &mut overview); 
         next_frame().await;
     }
 }
@@ -342,13 +699,17 @@ async  fn main_gui_runner(bema: Bema) {
 impl Runner for GuiRunner {
     fn run(&self, bema: &Bema) -> Result<()> {
 
-        macroquad::Window::new("Bema", main_gui_runner(bema.clone()));
+        for slide in &bema.slides {
+            check_images(&slide.items)?;
+        }
+        let highlighting = load_highlighting(bema)?;
+        macroquad::Window::new("Bema", main_gui_runner(bema.clone(), highlighting, self.start));
         Ok(())
     }
 }
 
 
-const CRT_FRAGMENT_SHADER: &'static str = r#"#version 100
+const CRT_FRAGMENT_SHADER: &str = r#"#version 100
 precision lowp float;
 varying vec4 color;
 varying vec2 uv;
@@ -361,7 +722,7 @@ void main() {
 "#;
 
 
-const CRT_FRAGMENT_SHADER_REVERSE_BLACK: &'static str = r#"#version 100
+const CRT_FRAGMENT_SHADER_REVERSE_BLACK: &str = r#"#version 100
 precision lowp float;
 varying vec4 color;
 varying vec2 uv;
@@ -375,7 +736,7 @@ void main() {
 }
 "#;
 
-const CRT_VERTEX_SHADER: &'static str = "#version 100
+const CRT_VERTEX_SHADER: &str = "#version 100
 attribute vec3 position;
 attribute vec2 texcoord;
 attribute vec4 color0;
@@ -1,49 +1,280 @@
-use crate::runner::Runner;
-use crate::bema::{Bema, SlideItem};
+use crate::runner::{Runner, output_path, table_widths};
+use crate::bema::{Bema, Slide, SlideItem, Layout, Align, VAlign, ImageSize};
+use crate::markup::{self, Span, SpanStyle};
+use crate::measure;
 use std::fs::File;
-use crossterm::Result;
-use std::io::Write;
+use std::path::PathBuf;
+use crate::error::Result;
+use std::io::{stdout, Write};
 
 pub struct HovercraftRunner {
+    // the deck is printed to stdout without an output directory
+    pub output: Option<PathBuf>,
+}
+
+fn color_role((r, g, b): (u8, u8, u8)) -> String {
+    format!("color-{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn align_class(align: Align) -> &'static str {
+    match align {
+        Align::Left => "align-left",
+        Align::Center => "align-center",
+        Align::Right => "align-right",
+    }
+}
+
+fn valign_class(valign: VAlign) -> &'static str {
+    match valign {
+        VAlign::Top => "valign-top",
+        VAlign::Middle => "valign-middle",
+        VAlign::Bottom => "valign-bottom",
+    }
+}
+
+// the classes of a slide, written in a field list before its title
+fn slide_classes(slide: &Slide) -> Vec<&'static str> {
+    let mut classes = vec![];
+    if slide.valign != VAlign::Top {
+        classes.push(valign_class(slide.valign));
+    }
+    if slide.layout == Layout::Image {
+        classes.push("layout-image");
+    }
+    classes
+}
+
+fn collect_colors(items: &[SlideItem], colors: &mut Vec<(u8, u8, u8)>) {
+    for item in items {
+        match item {
+            SlideItem::Text { text } => {
+                for span in markup::parse(text).iter().flatten() {
+                    if let Some(color) = span.style.color {
+                        if !colors.contains(&color) {
+                            colors.push(color);
+                        }
+                    }
+                }
+            },
+            SlideItem::Cols { items } | SlideItem::Rows { items } | SlideItem::Framed { items } | SlideItem::Aligned { items, .. } => collect_colors(items, colors),
+            _ => {},
+        }
+    }
+}
+
+// RST roles do not nest, a run gets the markup of its most specific style
+fn rst_span(span: &Span) -> String {
+    let style = span.style;
+    if style.code {
+        format!("``{}``", span.text)
+    } else if let Some(color) = style.color {
+        format!(":{}:`{}`", color_role(color), span.text)
+    } else if style.underline {
+        format!(":underline:`{}`", span.text)
+    } else if style.bold {
+        format!("**{}**", span.text)
+    } else if style.italic {
+        format!("*{}*", span.text)
+    } else {
+        span.text.clone()
+    }
+}
+
+fn rst_line(spans: &[Span]) -> String {
+    let mut line = String::new();
+    for (k, span) in spans.iter().enumerate() {
+        let text = rst_span(span);
+        let styled = span.style != SpanStyle::default() || (k > 0 && spans[k - 1].style != SpanStyle::default());
+        // inline markup has to be separated from the words around it, an escaped space does so invisibly
+        if styled && line.ends_with(|c: char| !c.is_whitespace()) && !text.starts_with(char::is_whitespace) {
+            line.push_str("\\ ");
+        }
+        line.push_str(&text);
+    }
+    line
 }
 
 impl HovercraftRunner {
-    fn render_item(&self, item: &SlideItem, img_i: &mut usize) -> Result<()> {
+    // the restructured text goes to `out`, images next to the output file
+    fn write(&self, out: &mut dyn Write, bema: &Bema) -> Result<()> {
+        let mut colors = vec![];
+        for slide in &bema.slides {
+            collect_colors(&slide.items, &mut colors);
+        }
+        // fields at the very start are attributes of the presentation, `:title:` being its html title
+        let metadata = &bema.metadata;
+        let fields = [("title", &metadata.title), ("author", &metadata.author), ("date", &metadata.date),
+            ("event", &metadata.event), ("license", &metadata.license)];
+        for (name, value) in fields.iter().filter(|(_, value)| !value.is_empty()) {
+            writeln!(out, ":{}: {}", name, value)?;
+        }
+        if fields.iter().any(|(_, value)| !value.is_empty()) {
+            writeln!(out)?;
+        }
+        writeln!(out, ".. role:: underline")?;
+        writeln!(out)?;
+        writeln!(out, ".. raw:: html")?;
+        writeln!(out)?;
+        write!(out, "   <style>.underline {{ text-decoration: underline; }}")?;
+        write!(out, " .align-left {{ text-align: left; }} .align-center {{ text-align: center; }} .align-right {{ text-align: right; }}")?;
+        // impress.js scales each step to the window, a slide is as high as the viewport
+        write!(out, " .valign-middle, .valign-bottom {{ display: flex; flex-direction: column; min-height: 100vh; }}")?;
+        write!(out, " .valign-middle {{ justify-content: center; }} .valign-bottom {{ justify-content: flex-end; }}")?;
+        write!(out, " .subtitle {{ font-size: 1.4em; font-style: italic; }} .byline {{ color: gray; }}")?;
+        write!(out, " .layout-image img {{ width: 100%; height: 100vh; object-fit: cover; }}")?;
+        for color in &colors {
+            write!(out, " .{} {{ color: rgb{:?}; }}", color_role(*color), color)?;
+        }
+        writeln!(out, "</style>")?;
+        writeln!(out)?;
+        for color in &colors {
+            writeln!(out, ".. role:: {}", color_role(*color))?;
+        }
+        writeln!(out)?;
+        let mut img_i = 0;
+        for (i, slide) in bema.slides.iter().enumerate() {
+            // fields before the title are attributes of the slide, the first slide needs a transition
+            // for them since docutils reads the preamble and a field list after it as body elements
+            let classes = slide_classes(slide);
+            if i > 0 || !classes.is_empty() {
+                writeln!(out, "----")?;
+            }
+            writeln!(out)?;
+            if !classes.is_empty() {
+                writeln!(out, ":class: {}", classes.join(" "))?;
+                writeln!(out)?;
+            }
+            // image slides have no title
+            if slide.layout != Layout::Image {
+                writeln!(out, "{}", slide.title)?;
+                // the underline has to be at least as wide as the title in columns
+                for _ in 0..measure::columns(&slide.title) {
+                    write!(out, "=")?;
+                }
+                writeln!(out)?;
+                writeln!(out)?;
+            }
+            if let Layout::Title { subtitle, byline } = &slide.layout {
+                for (class, text) in [("subtitle", subtitle), ("byline", byline)].iter().filter(|(_, text)| !text.is_empty()) {
+                    writeln!(out, ".. container:: {}", class)?;
+                    writeln!(out)?;
+                    // a line block keeps the lines of the byline apart
+                    for line in text.lines() {
+                        writeln!(out, "   | {}", line)?;
+                    }
+                    writeln!(out)?;
+                }
+            }
+            // the items of a slide that is not centred go in a container of its alignment
+            if slide.align == Align::Center {
+                for item in &slide.items {
+                    self.render_item(out, item, &mut img_i)?;
+                };
+            } else {
+                self.render_aligned(out, slide.align, &slide.items, &mut img_i)?;
+            }
+            if !slide.notes.is_empty() {
+                writeln!(out)?;
+                writeln!(out, ".. note::")?;
+                writeln!(out)?;
+                for line in slide.notes.split("\n") {
+                    writeln!(out, "  {}", line)?;
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    // the items in a container directive, indented below it
+    fn render_aligned(&self, out: &mut dyn Write, align: Align, items: &[SlideItem], img_i: &mut usize) -> Result<()> {
+        let mut content = vec![];
+        for item in items {
+            self.render_item(&mut content, item, img_i)?;
+        }
+        writeln!(out)?;
+        writeln!(out, ".. container:: {}", align_class(align))?;
+        writeln!(out)?;
+        for line in String::from_utf8_lossy(&content).lines() {
+            if line.is_empty() {
+                writeln!(out)?;
+            } else {
+                writeln!(out, "   {}", line)?;
+            }
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+
+    fn render_item(&self, out: &mut dyn Write, item: &SlideItem, img_i: &mut usize) -> Result<()> {
         match item {
-            SlideItem::Image { image, extension, width } => {
-                let file_path = format!("bema_{}{}", img_i, extension);
+            SlideItem::Image { image, extension, size } => {
+                let file_name = format!("bema_{}{}", img_i, extension);
                 *img_i += 1;
-                let mut buffer = File::create(&file_path)?;
+                let mut buffer = File::create(output_path(&self.output, &file_name)?)?;
                 buffer.write_all(image)?;
-                println!(".. image:: {}", &file_path);
-                width.map( |w| println!("   :width: {} px", w));
+                writeln!(out)?;
+                writeln!(out, ".. image:: {}", &file_name)?;
+                match size {
+                    ImageSize::Width(w) => writeln!(out, "   :width: {} px", w)?,
+                    ImageSize::Height(h) => writeln!(out, "   :height: {} px", h)?,
+                    ImageSize::WidthPercent(p) => writeln!(out, "   :width: {}%", p)?,
+                    ImageSize::Contain | ImageSize::Cover => writeln!(out, "   :width: 100%")?,
+                    // rst only has percentages of the line width
+                    ImageSize::Natural | ImageSize::HeightPercent(_) => {},
+                }
+                writeln!(out)?;
             },
             SlideItem::Code { extension, source } => {
-                println!(".. code:: {}", extension);
-                println!();
+                writeln!(out)?;
+                writeln!(out, ".. code:: {}", extension)?;
+                writeln!(out)?;
                 let splits = source.split("\n").map( |x| x.to_string()).collect::<Vec<_>>();
                 for split in splits {
-                    println!("  {}", split);
+                    writeln!(out, "  {}", split)?;
                 }
+                writeln!(out)?;
             },
             SlideItem::Text { text } => {
-                println!("{}", text);
+                for spans in markup::parse(text) {
+                    writeln!(out, "{}", rst_line(&spans))?;
+                }
             },
             SlideItem::Cols { items } => {
                 for item2 in items {
-                    self.render_item(item2, img_i)?;
+                    self.render_item(out, item2, img_i)?;
                 }
             },
             SlideItem::Rows { items } => {
                 for item2 in items {
-                    self.render_item(item2, img_i)?;
+                    self.render_item(out, item2, img_i)?;
                 }
             },
             SlideItem::Framed { items } => {
                 for item2 in items {
-                    self.render_item(item2, img_i)?;
+                    self.render_item(out, item2, img_i)?;
                 }
             },
+            SlideItem::Aligned { align, items } => self.render_aligned(out, *align, items, img_i)?,
+            SlideItem::Table { header, rows, .. } => {
+                // every directive follows a blank line, docutils would otherwise read it as part of the paragraph before it
+                writeln!(out)?;
+                writeln!(out, ".. list-table::")?;
+                if !header.is_empty() {
+                    writeln!(out, "   :header-rows: 1")?;
+                }
+                writeln!(out)?;
+                // docutils rejects a list-table whose rows do not all have the same number of cells
+                let columns = table_widths(header, rows).len();
+                for row in std::iter::once(header).filter(|h| !h.is_empty()).chain(rows.iter()) {
+                    for k in 0..columns {
+                        let line = format!("   {} - {}", if k == 0 { "*" } else { " " }, row.get(k).map_or("", |cell| cell.as_str()));
+                        writeln!(out, "{}", line.trim_end())?;
+                    }
+                }
+                writeln!(out)?;
+            },
+            SlideItem::Pause => {},
         }
         Ok(())
     }
@@ -51,21 +282,42 @@ impl HovercraftRunner {
 
 impl Runner for HovercraftRunner {
     fn run(&self, bema: &Bema) -> Result<()> {
-        for (i, slide) in bema.slides.iter().enumerate() {
-            if i > 0 { println!("----"); }
-            println!("");
-            println!("{}", slide.title);
-            for _ in 0..slide.title.len() {
-                print!("=");
-            }
-            println!("");
-            println!("");
-            let mut img_i = 0;
-            for item in &slide.items {
-                self.render_item(item, &mut img_i)?;
-            };
-            println!("");
+        match &self.output {
+            Some(_) => {
+                let rst_path = output_path(&self.output, "bema.rst")?;
+                self.write(&mut File::create(&rst_path)?, bema)?;
+                println!("export hovercraft: {}", rst_path.display());
+            },
+            None => self.write(&mut stdout(), bema)?,
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Helper;
+
+    fn rst(bema: &Bema) -> String {
+        let mut out = vec![];
+        HovercraftRunner { output: None }.write(&mut out, bema).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn first_slide_fields_follow_a_transition() {
+        let out = rst(&crate::slides(|b| b.title("Talk").title_slide("Bema", "slides", "", "")));
+        let (preamble, first) = out.split_once("\n----\n").unwrap();
+        assert!(preamble.starts_with(":title: Talk\n\n"));
+        assert!(!preamble.contains(":class:"));
+        assert!(first.starts_with("\n:class: valign-middle\n\nBema\n"));
+    }
+
+    #[test]
+    fn directives_follow_a_blank_line() {
+        let out = rst(&crate::slides(|b| b.slide("Table", |s| s.text("before").table(&["a"], &[Align::Left], &[&["1"]]).text("after"))));
+        assert!(out.contains("before\n\n.. list-table::"));
+        assert!(!out.contains("\n----\n"));
+    }
+}
@@ -0,0 +1,321 @@
+use crate::runner::{Runner, Highlighting, load_highlighting, output_path, chrome};
+use crate::bema::{Bema, SlideItem, Layout, Align, VAlign, ImageSize, CodeOverflow};
+use crate::markup::{self, Span};
+use crate::theme::Theme;
+use std::fs::File;
+use std::path::PathBuf;
+use std::io::Write;
+use crate::error::Result;
+
+use syntect::html::highlighted_html_for_string;
+use syntect::highlighting::Theme as CodeTheme;
+
+pub struct HtmlRunner {
+    pub output: Option<PathBuf>,
+    // slide shown when the page has no slide number in its url
+    pub start: usize,
+}
+
+fn escape(text: &str) -> String {
+    text.replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(">", "&gt;")
+        .replace("\"", "&quot;")
+}
+
+fn mime_type(extension: &str) -> String {
+    match extension.replace(".", "").to_lowercase().as_str() {
+        "jpg" | "jpeg" => "image/jpeg".to_string(),
+        "svg" => "image/svg+xml".to_string(),
+        other => format!("image/{}", other),
+    }
+}
+
+fn span_html(span: &Span) -> String {
+    let style = span.style;
+    let mut html = escape(&span.text);
+    if style.code { html = format!("<code>{}</code>", html); }
+    if style.bold { html = format!("<b>{}</b>", html); }
+    if style.italic { html = format!("<i>{}</i>", html); }
+    if style.underline { html = format!("<u>{}</u>", html); }
+    if let Some((r, g, b)) = style.color {
+        html = format!("<span style=\"color: rgb({}, {}, {})\">{}</span>", r, g, b, html);
+    }
+    html
+}
+
+fn css_color((r, g, b): (u8, u8, u8)) -> String {
+    format!("rgb({}, {}, {})", r, g, b)
+}
+
+fn theme_css(theme: &Theme) -> String {
+    format!(concat!(
+        "body {{ background: {}; color: {}; }}\n",
+        "h1 {{ color: {}; }}\n",
+        ".index, .footer, .byline {{ color: {}; }}\n",
+        ".subtitle {{ color: {}; }}\n",
+        ".framed, th, td {{ border-color: {}; }}\n",
+        ".text code {{ color: {}; }}\n"),
+        css_color(theme.background), css_color(theme.text), css_color(theme.title),
+        css_color(theme.progress), css_color(theme.accent), css_color(theme.frame), css_color(theme.accent))
+}
+
+// flex alignment of the items of a container, inherited by the containers inside it
+fn flex_align(align: Align) -> &'static str {
+    match align {
+        Align::Left => "flex-start",
+        Align::Center => "center",
+        Align::Right => "flex-end",
+    }
+}
+
+// lines wider than the slide: wrapped, cut with an ellipsis, or left to scroll
+fn code_css(overflow: CodeOverflow) -> &'static str {
+    match overflow {
+        CodeOverflow::Overflow => ".code pre { overflow-x: auto; }\n",
+        CodeOverflow::Wrap => ".code pre { white-space: pre-wrap; }\n",
+        CodeOverflow::Truncate => ".code pre { overflow: hidden; text-overflow: ellipsis; }\n",
+    }
+}
+
+impl HtmlRunner {
+    fn render_item(&self, item: &SlideItem, highlighting: &Highlighting, theme: &CodeTheme, step: &mut usize, html: &mut String) -> Result<()> {
+        // items after a pause are wrapped so that the script can reveal them one step at a time
+        if let SlideItem::Pause = item {
+            *step += 1;
+            return Ok(());
+        }
+        if *step > 0 {
+            html.push_str(&format!("<div class=\"step\" data-step=\"{}\">\n", step));
+        }
+        let wrapped = *step > 0;
+        match item {
+            SlideItem::Image { image, extension, size } => {
+                let style = match size {
+                    ImageSize::Natural => String::new(),
+                    ImageSize::Width(w) => format!(" style=\"width: {}px\"", w),
+                    ImageSize::Height(h) => format!(" style=\"height: {}px\"", h),
+                    ImageSize::WidthPercent(p) => format!(" style=\"width: {}%\"", p),
+                    // the slide below its title is roughly three quarters of the window
+                    ImageSize::HeightPercent(p) => format!(" style=\"height: {}vh\"", p * 0.75),
+                    ImageSize::Contain => " style=\"width: 100%; height: 75vh; object-fit: contain\"".to_string(),
+                    ImageSize::Cover => " style=\"width: 100%; height: 75vh; object-fit: cover\"".to_string(),
+                };
+                html.push_str(&format!("<img src=\"data:{};base64,{}\"{}>\n", mime_type(extension), base64::encode(image), style));
+            },
+            SlideItem::Code { extension, source } => {
+                let syntax = highlighting.syntax(extension);
+                html.push_str("<div class=\"code\">");
+                html.push_str(&highlighted_html_for_string(source, &highlighting.syntaxes, syntax, theme));
+                html.push_str("</div>\n");
+            },
+            SlideItem::Text { text } => {
+                let lines = markup::parse(text).iter()
+                    .map(|spans| spans.iter().map(span_html).collect::<String>())
+                    .collect::<Vec<_>>();
+                html.push_str(&format!("<div class=\"text\">{}</div>\n", lines.join("\n")));
+            },
+            SlideItem::Cols { items } => {
+                html.push_str("<div class=\"cols\">\n");
+                for item2 in items {
+                    self.render_item(item2, highlighting, theme, step, html)?;
+                }
+                html.push_str("</div>\n");
+            },
+            SlideItem::Rows { items } => {
+                html.push_str("<div class=\"rows\">\n");
+                for item2 in items {
+                    self.render_item(item2, highlighting, theme, step, html)?;
+                }
+                html.push_str("</div>\n");
+            },
+            SlideItem::Framed { items } => {
+                html.push_str("<div class=\"framed\">\n");
+                for item2 in items {
+                    self.render_item(item2, highlighting, theme, step, html)?;
+                }
+                html.push_str("</div>\n");
+            },
+            SlideItem::Aligned { align, items } => {
+                html.push_str(&format!("<div class=\"aligned\" style=\"--align: {}\">\n", flex_align(*align)));
+                for item2 in items {
+                    self.render_item(item2, highlighting, theme, step, html)?;
+                }
+                html.push_str("</div>\n");
+            },
+            SlideItem::Table { header, rows, align } => {
+                let cell = |tag: &str, k: usize, text: &String| {
+                    let text_align = match align.get(k).unwrap_or(&Align::Left) {
+                        Align::Left => "left",
+                        Align::Center => "center",
+                        Align::Right => "right",
+                    };
+                    format!("<{} style=\"text-align: {}\">{}</{}>", tag, text_align, escape(text), tag)
+                };
+                html.push_str("<table>\n");
+                if !header.is_empty() {
+                    let cells = header.iter().enumerate().map(|(k, h)| cell("th", k, h)).collect::<String>();
+                    html.push_str(&format!("<tr>{}</tr>\n", cells));
+                }
+                for row in rows {
+                    let cells = row.iter().enumerate().map(|(k, c)| cell("td", k, c)).collect::<String>();
+                    html.push_str(&format!("<tr>{}</tr>\n", cells));
+                }
+                html.push_str("</table>\n");
+            },
+            SlideItem::Pause => {},
+        }
+        if wrapped {
+            html.push_str("</div>\n");
+        }
+        Ok(())
+    }
+}
+
+impl Runner for HtmlRunner {
+    fn run(&self, bema: &Bema) -> Result<()> {
+        let highlighting = load_highlighting(bema)?;
+        let theme = highlighting.theme(&bema.theme);
+
+        let mut html = String::new();
+        let metadata = &bema.metadata;
+        let title = if metadata.title.is_empty() { "bema" } else { &metadata.title };
+        let mut head = format!("<title>{}</title>\n", escape(title));
+        for (name, content) in [("author", &metadata.author), ("date", &metadata.date), ("license", &metadata.license)].iter() {
+            if !content.is_empty() {
+                head.push_str(&format!("<meta name=\"{}\" content=\"{}\">\n", name, escape(content)));
+            }
+        }
+        html.push_str(&HTML_HEADER
+            .replace("<title>bema</title>\n", &head)
+            .replace("</style>", &format!("{}{}</style>", theme_css(&bema.theme), code_css(bema.code_overflow))));
+        for (i, slide) in bema.slides.iter().enumerate() {
+            let valign = match slide.valign {
+                VAlign::Top => "top",
+                VAlign::Middle => "middle",
+                VAlign::Bottom => "bottom",
+            };
+            let layout = match slide.layout {
+                Layout::Content => "content",
+                Layout::Title { .. } => "title",
+                Layout::Section => "section",
+                Layout::Image => "image",
+                Layout::Closing => "closing",
+            };
+            html.push_str(&format!("<section class=\"slide {} {}\" id=\"{}\" style=\"--align: {}\">\n",
+                valign, layout, i + 1, flex_align(slide.align)));
+            // image slides have no index, title or footer
+            let chrome_shown = slide.layout != Layout::Image;
+            if chrome_shown {
+                html.push_str(&format!("<div class=\"index\">{}</div>\n", escape(&chrome(&bema.header, bema, i))));
+                html.push_str(&format!("<h1>{}</h1>\n", escape(&slide.title)));
+            }
+            if let Layout::Title { subtitle, byline } = &slide.layout {
+                if !subtitle.is_empty() {
+                    html.push_str(&format!("<div class=\"subtitle\">{}</div>\n", escape(subtitle)));
+                }
+                if !byline.is_empty() {
+                    html.push_str(&format!("<div class=\"byline\">{}</div>\n", escape(byline)));
+                }
+            }
+            let mut step = 0;
+            for item in &slide.items {
+                self.render_item(item, &highlighting, theme, &mut step, &mut html)?;
+            }
+            let footer = chrome(&bema.footer, bema, i);
+            if chrome_shown && !footer.is_empty() {
+                html.push_str(&format!("<div class=\"footer\">{}</div>\n", escape(&footer)));
+            }
+            html.push_str("</section>\n");
+        }
+        html.push_str(&HTML_FOOTER.replace("START", &(self.start + 1).to_string()));
+
+        let html_path = output_path(&self.output, "bema.html")?;
+        let mut file = File::create(&html_path)?;
+        file.write_all(html.as_bytes())?;
+        println!("export html: {}", html_path.display());
+        Ok(())
+    }
+}
+
+const HTML_HEADER: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>bema</title>
+<style>
+body { margin: 0; background: black; color: white; font-family: monospace; font-size: 3vmin; }
+.slide { display: none; flex-direction: column; align-items: var(--align); min-height: 100vh; box-sizing: border-box; padding: 2vmin; position: relative; }
+.slide.current { display: flex; }
+.index { align-self: flex-start; color: gray; font-size: 0.7em; }
+.footer { position: absolute; bottom: 1vmin; left: 0; right: 0; text-align: center; color: gray; font-size: 0.7em; }
+h1 { font-size: 2em; }
+.subtitle { font-size: 1.4em; font-style: italic; white-space: pre-wrap; }
+.byline { margin-top: 1em; color: gray; white-space: pre-wrap; }
+.slide.image { padding: 0; }
+.slide.image img { width: 100vw !important; height: 100vh !important; }
+/* the index stays at the top, auto margins move the rest of the slide down */
+.slide.middle h1, .slide.bottom h1 { margin-top: auto; }
+.slide.middle::after { content: ""; margin-bottom: auto; }
+.text { white-space: pre-wrap; min-height: 1em; text-align: left; max-width: 100%; }
+.code { max-width: 100%; }
+.code pre { padding: 1em; }
+.cols { display: flex; flex-direction: row; justify-content: space-around; align-items: flex-start; width: 100%; }
+.cols > * { flex: 1; display: flex; flex-direction: column; align-items: var(--align); }
+.rows { display: flex; flex-direction: column; align-items: var(--align); }
+.aligned { display: flex; flex-direction: column; align-items: var(--align); align-self: stretch; }
+.framed { display: flex; flex-direction: column; align-items: var(--align); border: 2px solid white; padding: 0.5em; margin: 0.2em; }
+table { border-collapse: collapse; margin: 0.5em; }
+th, td { border: 2px solid white; padding: 0 0.5em; white-space: pre; }
+img { max-width: 100%; }
+.step { display: contents; }
+.step.hidden { visibility: hidden; }
+</style>
+</head>
+<body>
+"#;
+
+const HTML_FOOTER: &str = r##"<script>
+var slides = document.querySelectorAll(".slide");
+var current = 0;
+var step = 0;
+function steps(i) {
+  var max = 0;
+  slides[i].querySelectorAll(".step").forEach(function(e) { max = Math.max(max, parseInt(e.dataset.step)); });
+  return max;
+}
+function reveal() {
+  slides[current].querySelectorAll(".step").forEach(function(e) {
+    e.classList.toggle("hidden", parseInt(e.dataset.step) > step);
+  });
+}
+function show(i, s) {
+  if (i >= slides.length) { i = 0; }
+  if (i < 0) { i = slides.length - 1; }
+  slides[current].classList.remove("current");
+  current = i;
+  step = s ? steps(current) : 0;
+  slides[current].classList.add("current");
+  reveal();
+  history.replaceState(null, "", "#" + (current + 1));
+}
+function next() {
+  if (step < steps(current)) { step++; reveal(); } else { show(current + 1); }
+}
+function previous() {
+  if (step > 0) { step--; reveal(); } else { show(current - 1, true); }
+}
+document.addEventListener("keydown", function(e) {
+  switch (e.key) {
+    case "ArrowRight": case "ArrowDown": case "l": case "j": case "n": case " ": next(); break;
+    case "ArrowLeft": case "ArrowUp": case "h": case "k": case "p": previous(); break;
+    case "g": show(0); break;
+    case "G": show(slides.length - 1); break;
+  }
+});
+document.addEventListener("click", function() { next(); });
+show((parseInt(location.hash.substring(1)) || START) - 1);
+</script>
+</body>
+</html>
+"##;
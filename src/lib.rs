@@ -1,50 +1,203 @@
 mod runner;
-use crate::runner::{Runner};
 mod hovercraft_runner;
-use crate::hovercraft_runner::HovercraftRunner;
+mod cell_buffer;
 mod terminal_runner;
-use crate::terminal_runner::TerminalRunner;
+mod graphics;
 mod gui_runner;
-use crate::gui_runner::GuiRunner;
+mod html_runner;
+mod pdf_runner;
+mod cli;
+mod navigation;
 mod bema;
-use crate::bema::{Bema, SlideItem, Slide};
+use crate::bema::{Bema, SlideItem, Slide, HighlightSource, DEFAULT_HEADER};
+pub use crate::bema::{Align, VAlign, ImageSize, CodeOverflow, Metadata, Layout};
+mod markdown;
+mod markup;
+mod measure;
+mod theme;
+mod error;
+pub use crate::error::Error;
+pub use crate::theme::Theme;
 
 use std::env;
+use std::path::{Path, PathBuf};
 
-use crossterm::Result;
+use crate::error::Result;
 
 pub fn slides(f: fn(Bema) -> Bema) -> Bema {
     f(Bema { 
         slides: vec![],
+        source: None,
+        theme: Theme::dark(),
+        alternate_theme: Theme::light(),
+        highlighting: vec![],
+        code_overflow: CodeOverflow::Overflow,
+        metadata: Metadata::default(),
+        header: DEFAULT_HEADER.to_string(),
+        footer: String::new(),
     })
 }
 
+pub fn load(path: &str) -> Result<Bema> {
+    markdown::load(Path::new(path))
+}
+
 impl Slide {
+    fn new(title: &str) -> Slide {
+        Slide {
+            title: String::from(title),
+            items: vec![],
+            notes: String::new(),
+            align: Align::Center,
+            valign: VAlign::Top,
+            layout: Layout::Content,
+        }
+    }
+
+    fn layout(mut self, layout: Layout) -> Slide {
+        self.layout = layout;
+        self
+    }
+
+    pub fn notes(mut self, notes: &str) -> Slide {
+        if !self.notes.is_empty() {
+            self.notes.push('\n');
+        }
+        self.notes.push_str(notes);
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Slide {
+        self.align = align;
+        self
+    }
+
+    pub fn valign(mut self, valign: VAlign) -> Slide {
+        self.valign = valign;
+        self
+    }
 }
 
 impl Bema {
     pub fn slide(mut self, title: &str, f: fn(Slide) -> Slide) -> Bema {
+        self.slides.push(f(Slide::new(title)));
+        self
+    }
 
-        let s = Slide {
-            title: String::from(title),
-            items: vec![],
-        };
-        self.slides.push(f(s));
+    // layouts are made of ordinary items, every runner draws them without knowing about them
+
+    // the title in the middle of the screen, the subtitle below it then the author and date;
+    // empty parts are left out
+    pub fn title_slide(mut self, title: &str, subtitle: &str, author: &str, date: &str) -> Bema {
+        let byline = [author, date].iter().filter(|part| !part.is_empty()).cloned().collect::<Vec<_>>().join("\n");
+        let layout = Layout::Title { subtitle: subtitle.to_string(), byline };
+        self.slides.push(Slide::new(title).valign(VAlign::Middle).layout(layout));
+        self
+    }
+
+    // a title alone in the middle of the screen, opening a part of the deck
+    pub fn section(mut self, title: &str) -> Bema {
+        self.slides.push(Slide::new(title).valign(VAlign::Middle).layout(Layout::Section));
+        self
+    }
+
+    // a title above two columns of items
+    pub fn two_columns(mut self, title: &str, left: fn(SlideItems) -> SlideItems, right: fn(SlideItems) -> SlideItems) -> Bema {
+        let column = |f: fn(SlideItems) -> SlideItems| SlideItem::Rows { items: f(SlideItems { items: vec![] }).items };
+        self.slides.push(Slide::new(title).push(SlideItem::Cols { items: vec![column(left), column(right)] }));
+        self
+    }
+
+    // an untitled slide filled with an image, cropped to the shape of the screen
+    pub fn image_slide(mut self, image: Vec<u8>, extension: &str) -> Bema {
+        self.slides.push(Slide::new("").image(image, extension, ImageSize::Cover).layout(Layout::Image));
+        self
+    }
+
+    // a title in the middle of the screen with a few items below it, such as thanks and contacts
+    pub fn closing(mut self, title: &str, f: fn(Slide) -> Slide) -> Bema {
+        self.slides.push(f(Slide::new(title).valign(VAlign::Middle).layout(Layout::Closing)));
+        self
+    }
+
+    pub fn theme(mut self, theme: Theme) -> Bema {
+        self.alternate_theme = theme.counterpart();
+        self.theme = theme;
+        self
+    }
+
+    pub fn alternate_theme(mut self, theme: Theme) -> Bema {
+        self.alternate_theme = theme;
         self
     }
 
+    pub fn syntax(mut self, sublime_syntax: &str) -> Bema {
+        self.highlighting.push(HighlightSource::Syntax(sublime_syntax.to_string()));
+        self
+    }
+
+    pub fn code_theme(mut self, name: &str, tm_theme: &[u8]) -> Bema {
+        self.highlighting.push(HighlightSource::CodeTheme { name: name.to_string(), tm_theme: tm_theme.to_vec() });
+        self
+    }
+
+    pub fn highlighting_dir(mut self, path: &str) -> Bema {
+        self.highlighting.push(HighlightSource::Dir(PathBuf::from(path)));
+        self
+    }
+
+    pub fn code_overflow(mut self, overflow: CodeOverflow) -> Bema {
+        self.code_overflow = overflow;
+        self
+    }
+
+    // the title of the talk, as opposed to the title of a slide
+    pub fn title(mut self, title: &str) -> Bema {
+        self.metadata.title = title.to_string();
+        self
+    }
+
+    pub fn author(mut self, author: &str) -> Bema {
+        self.metadata.author = author.to_string();
+        self
+    }
+
+    pub fn date(mut self, date: &str) -> Bema {
+        self.metadata.date = date.to_string();
+        self
+    }
+
+    pub fn event(mut self, event: &str) -> Bema {
+        self.metadata.event = event.to_string();
+        self
+    }
+
+    pub fn license(mut self, license: &str) -> Bema {
+        self.metadata.license = license.to_string();
+        self
+    }
+
+    // a template such as `{author} · {event} · {n}/{total}`, `{n}/{total}` by default
+    pub fn header(mut self, template: &str) -> Bema {
+        self.header = template.to_string();
+        self
+    }
+
+    // a template like the header, empty by default
+    pub fn footer(mut self, template: &str) -> Bema {
+        self.footer = template.to_string();
+        self
+    }
+
+    // runs the deck according to the program arguments, see `run_with_args`
     pub fn run(&self) -> Result<()> {
-        if env::args().len() == 2 {
-            let args : Vec<String> = env::args().collect();
-            match args[1].as_str() {
-                "hovercraft" => HovercraftRunner { }.run(&self)?,
-                "gui" => GuiRunner { }.run(&self)?,
-                _ => {}
-            }
-        } else {
-            TerminalRunner { }.run(&self)?;
-        }
-        Ok(())
+        self.run_with_args(&env::args().skip(1).collect::<Vec<_>>())
+    }
+
+    // runs the deck according to `args`, the command line arguments without the program name
+    pub fn run_with_args<S: AsRef<str>>(&self, args: &[S]) -> Result<()> {
+        let args = args.iter().map(|arg| arg.as_ref().to_string()).collect::<Vec<_>>();
+        cli::run(self, &args)
     }
 }
 
@@ -67,8 +220,9 @@ pub trait Helper {
         self.push(SlideItem::Code { extension: String::from(extension), source: String::from(source) })
     }
 
-    fn image(self, image: Vec<u8>, extension: &str, width: Option<usize>) -> Self where Self: Sized {
-        self.push(SlideItem::Image { image, extension: String::from(extension), width })
+    // `size` is an `ImageSize`, or an optional width in pixels
+    fn image<S: Into<ImageSize>>(self, image: Vec<u8>, extension: &str, size: S) -> Self where Self: Sized {
+        self.push(SlideItem::Image { image, extension: String::from(extension), size: size.into() })
     }
 
     fn cols(self, f: fn(SlideItems) -> SlideItems) -> Self where Self: Sized {
@@ -82,6 +236,22 @@ pub trait Helper {
     fn framed(self, f: fn(SlideItems) -> SlideItems) -> Self where Self: Sized {
         self.push(SlideItem::Framed { items: f(SlideItems { items: vec![]}).items })
     }
+
+    fn aligned(self, align: Align, f: fn(SlideItems) -> SlideItems) -> Self where Self: Sized {
+        self.push(SlideItem::Aligned { align, items: f(SlideItems { items: vec![]}).items })
+    }
+
+    fn pause(self) -> Self where Self: Sized {
+        self.push(SlideItem::Pause)
+    }
+
+    fn table(self, header: &[&str], align: &[Align], rows: &[&[&str]]) -> Self where Self: Sized {
+        self.push(SlideItem::Table {
+            header: header.iter().map(|h| h.to_string()).collect(),
+            rows: rows.iter().map(|row| row.iter().map(|c| c.to_string()).collect()).collect(),
+            align: align.to_vec(),
+        })
+    }
 }
 
 impl Helper for SlideItems {
@@ -0,0 +1,404 @@
+use crate::bema::{Bema, SlideItem, Slide, Layout, Align, VAlign, ImageSize, CodeOverflow, Metadata, DEFAULT_HEADER};
+use crate::theme::Theme;
+use std::fs;
+use std::path::Path;
+use crate::error::{Error, Result};
+
+enum ContainerKind {
+    Slide,
+    Cols,
+    Rows,
+    Framed,
+    Aligned(Align),
+}
+
+struct Container {
+    kind: ContainerKind,
+    items: Vec<SlideItem>,
+    blank: bool,
+}
+
+impl Container {
+    fn new(kind: ContainerKind) -> Container {
+        Container { kind, items: vec![], blank: false }
+    }
+
+    fn push(&mut self, item: SlideItem) {
+        if self.blank && !self.items.is_empty() {
+            self.items.push(SlideItem::Text { text: String::new() });
+        }
+        self.blank = false;
+        self.items.push(item);
+    }
+
+    fn into_item(self) -> SlideItem {
+        match self.kind {
+            ContainerKind::Cols => SlideItem::Cols { items: self.items },
+            ContainerKind::Framed => SlideItem::Framed { items: self.items },
+            ContainerKind::Aligned(align) => SlideItem::Aligned { align, items: self.items },
+            _ => SlideItem::Rows { items: self.items },
+        }
+    }
+}
+
+struct SlideParser<'a> {
+    base_dir: &'a Path,
+    title: String,
+    align: Align,
+    valign: VAlign,
+    notes: Vec<String>,
+    stack: Vec<Container>,
+    paragraph: Vec<String>,
+    table: Vec<String>,
+    code: Option<(String, String)>,
+    in_notes: bool,
+}
+
+fn split_table_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    line.split('|').map(|cell| cell.trim().to_string()).collect()
+}
+
+fn parse_table(lines: &[String]) -> SlideItem {
+    let mut rows: Vec<Vec<String>> = lines.iter().map(|line| split_table_row(line)).collect();
+    let is_separator = |row: &Vec<String>| row.iter().all(|cell| !cell.is_empty() && cell.chars().all(|c| c == '-' || c == ':'));
+    if rows.len() > 1 && is_separator(&rows[1]) {
+        let align = rows.remove(1).iter().map(|cell| match (cell.starts_with(':'), cell.ends_with(':')) {
+            (true, true) => Align::Center,
+            (false, true) => Align::Right,
+            _ => Align::Left,
+        }).collect();
+        let header = rows.remove(0);
+        SlideItem::Table { header, rows, align }
+    } else {
+        SlideItem::Table { header: vec![], rows, align: vec![] }
+    }
+}
+
+// `{width=500}`, `{height=300}`, `{width=50%}`, `{height=40%}`, `{fit=contain}` or `{fit=cover}`
+fn parse_image_size(attributes: &str) -> ImageSize {
+    let attribute = attributes.strip_prefix('{').and_then(|a| a.strip_suffix('}')).unwrap_or("");
+    let (name, value) = match attribute.find('=') {
+        Some(k) => (attribute[..k].trim(), attribute[k + 1..].trim()),
+        None => return ImageSize::Natural,
+    };
+    let percent = value.strip_suffix('%').and_then(|p| p.trim().parse().ok());
+    let size = match (name, value, percent) {
+        ("width", _, Some(p)) => Some(ImageSize::WidthPercent(p)),
+        ("height", _, Some(p)) => Some(ImageSize::HeightPercent(p)),
+        ("width", w, None) => w.parse().ok().map(ImageSize::Width),
+        ("height", h, None) => h.parse().ok().map(ImageSize::Height),
+        ("fit", "contain", _) => Some(ImageSize::Contain),
+        ("fit", "cover", _) => Some(ImageSize::Cover),
+        _ => None,
+    };
+    size.unwrap_or(ImageSize::Natural)
+}
+
+fn parse_align(name: &str) -> Option<Align> {
+    match name {
+        "left" => Some(Align::Left),
+        "center" => Some(Align::Center),
+        "right" => Some(Align::Right),
+        _ => None,
+    }
+}
+
+// a title ending with `{align=left valign=middle}`, either attribute being optional
+fn parse_title(line: &str) -> (String, Option<Align>, Option<VAlign>) {
+    let attributes = match (line.rfind('{'), line.ends_with('}')) {
+        (Some(k), true) => (k, &line[k + 1..line.len() - 1]),
+        _ => return (line.to_string(), None, None),
+    };
+    let (mut align, mut valign) = (None, None);
+    for attribute in attributes.1.split_whitespace() {
+        match attribute.split_once('=') {
+            Some(("align", name)) if parse_align(name).is_some() => align = parse_align(name),
+            Some(("valign", "top")) => valign = Some(VAlign::Top),
+            Some(("valign", "middle")) => valign = Some(VAlign::Middle),
+            Some(("valign", "bottom")) => valign = Some(VAlign::Bottom),
+            _ => return (line.to_string(), None, None),
+        }
+    }
+    // braces without any attribute are part of the title
+    if align.is_none() && valign.is_none() {
+        return (line.to_string(), None, None);
+    }
+    (line[..attributes.0].trim().to_string(), align, valign)
+}
+
+fn parse_image(line: &str) -> Option<(String, ImageSize)> {
+    let rest = line.strip_prefix("![")?;
+    let path_start = rest.find("](")? + 2;
+    let path_end = path_start + rest[path_start..].find(')')?;
+    let path = rest[path_start..path_end].trim().to_string();
+    Some((path, parse_image_size(rest[path_end + 1..].trim())))
+}
+
+impl<'a> SlideParser<'a> {
+    fn new(base_dir: &'a Path) -> SlideParser<'a> {
+        SlideParser {
+            base_dir,
+            title: String::new(),
+            align: Align::Center,
+            valign: VAlign::Top,
+            notes: vec![],
+            stack: vec![Container::new(ContainerKind::Slide)],
+            paragraph: vec![],
+            table: vec![],
+            code: None,
+            in_notes: false,
+        }
+    }
+
+    fn push(&mut self, item: SlideItem) {
+        self.stack.last_mut().unwrap().push(item);
+    }
+
+    fn flush_paragraph(&mut self) {
+        if !self.table.is_empty() {
+            let item = parse_table(&self.table);
+            self.table.clear();
+            self.push(item);
+        }
+        if !self.paragraph.is_empty() {
+            let text = self.paragraph.join("\n");
+            self.paragraph.clear();
+            self.push(SlideItem::Text { text });
+        }
+    }
+
+    fn push_paragraph_line(&mut self, line: String) {
+        if !self.table.is_empty() {
+            self.flush_paragraph();
+        }
+        self.paragraph.push(line);
+    }
+
+    fn close_container(&mut self) {
+        self.flush_paragraph();
+        if self.stack.len() > 1 {
+            let item = self.stack.pop().unwrap().into_item();
+            self.push(item);
+        }
+    }
+
+    fn line(&mut self, line: &str) -> Result<()> {
+        if let Some((extension, source)) = &mut self.code {
+            if line.trim_start().starts_with("```") {
+                let item = SlideItem::Code { extension: extension.clone(), source: source.clone() };
+                self.code = None;
+                self.push(item);
+            } else {
+                source.push_str(line);
+                source.push('\n');
+            }
+            return Ok(());
+        }
+        let trimmed = line.trim();
+        if self.in_notes {
+            self.notes.push(line.to_string());
+        } else if trimmed == "???" {
+            self.flush_paragraph();
+            self.in_notes = true;
+        } else if let Some(extension) = trimmed.strip_prefix("```") {
+            self.flush_paragraph();
+            self.code = Some((extension.trim().to_string(), String::new()));
+        } else if let Some(title) = trimmed.strip_prefix("# ") {
+            self.flush_paragraph();
+            let (title, align, valign) = parse_title(title.trim());
+            self.title = title;
+            self.align = align.unwrap_or(self.align);
+            self.valign = valign.unwrap_or(self.valign);
+        } else if let Some(kind) = trimmed.strip_prefix(":::") {
+            self.flush_paragraph();
+            match kind.trim() {
+                "cols" => self.stack.push(Container::new(ContainerKind::Cols)),
+                "rows" => self.stack.push(Container::new(ContainerKind::Rows)),
+                "frame" | "framed" => self.stack.push(Container::new(ContainerKind::Framed)),
+                kind => match parse_align(kind) {
+                    Some(align) => self.stack.push(Container::new(ContainerKind::Aligned(align))),
+                    None => self.close_container(),
+                },
+            }
+        } else if trimmed == ". . ." {
+            self.flush_paragraph();
+            self.push(SlideItem::Pause);
+        } else if let Some((path, size)) = parse_image(trimmed) {
+            self.flush_paragraph();
+            let image = fs::read(self.base_dir.join(&path))?;
+            let extension = Path::new(&path).extension()
+                .map(|e| format!(".{}", e.to_string_lossy()))
+                .unwrap_or_default();
+            self.push(SlideItem::Image { image, extension, size });
+        } else if trimmed.starts_with('|') {
+            if !self.paragraph.is_empty() {
+                self.flush_paragraph();
+            }
+            self.table.push(trimmed.to_string());
+        } else if trimmed.is_empty() {
+            self.flush_paragraph();
+            self.stack.last_mut().unwrap().blank = true;
+        } else if let Some(bullet) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* ")) {
+            self.push_paragraph_line(format!("• {}", bullet));
+        } else {
+            self.push_paragraph_line(line.trim_end().to_string());
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Slide {
+        if let Some((extension, source)) = self.code.take() {
+            self.push(SlideItem::Code { extension, source });
+        }
+        self.flush_paragraph();
+        while self.stack.len() > 1 {
+            self.close_container();
+        }
+        let notes = self.notes.join("\n").trim().to_string();
+        Slide {
+            title: self.title,
+            items: self.stack.pop().unwrap().items,
+            notes,
+            align: self.align,
+            valign: self.valign,
+            layout: Layout::Content,
+        }
+    }
+}
+
+// `key: value` lines between two `---` lines at the very start of the file, the number of lines they take
+fn parse_front_matter(source: &str) -> (Vec<(String, String)>, usize) {
+    let mut lines = source.lines();
+    if lines.next().map(|line| line.trim()) != Some("---") {
+        return (vec![], 0);
+    }
+    let keys = ["title", "author", "date", "event", "license", "header", "footer"];
+    let mut fields = vec![];
+    for line in lines {
+        if line.trim() == "---" {
+            let taken = fields.len() + 2;
+            return (fields, taken);
+        }
+        match line.split_once(':') {
+            Some((key, value)) if keys.contains(&key.trim()) => fields.push((key.trim().to_string(), value.trim().to_string())),
+            // a first slide rather than front matter
+            _ => return (vec![], 0),
+        }
+    }
+    (vec![], 0)
+}
+
+pub fn parse(source: &str, base_dir: &Path) -> Result<Bema> {
+    let (fields, skipped) = parse_front_matter(source);
+    let mut metadata = Metadata::default();
+    let (mut header, mut footer) = (DEFAULT_HEADER.to_string(), String::new());
+    for (key, value) in fields {
+        match key.as_str() {
+            "title" => metadata.title = value,
+            "author" => metadata.author = value,
+            "date" => metadata.date = value,
+            "event" => metadata.event = value,
+            "license" => metadata.license = value,
+            "header" => header = value,
+            _ => footer = value,
+        }
+    }
+
+    let mut slides = vec![];
+    let mut parser = SlideParser::new(base_dir);
+    for line in source.lines().skip(skipped) {
+        if parser.code.is_none() && line.trim() == "---" {
+            slides.push(parser.finish());
+            parser = SlideParser::new(base_dir);
+        } else {
+            parser.line(line)?;
+        }
+    }
+    slides.push(parser.finish());
+    slides.retain(|slide| !slide.title.is_empty() || !slide.items.is_empty() || !slide.notes.is_empty());
+    if slides.is_empty() {
+        return Err(Error::Deck("deck has no slides".to_string()));
+    }
+    Ok(Bema { slides, source: None, theme: Theme::dark(), alternate_theme: Theme::light(), highlighting: vec![], code_overflow: CodeOverflow::Overflow,
+        metadata, header, footer })
+}
+
+pub fn load(path: &Path) -> Result<Bema> {
+    let source = fs::read_to_string(path)?;
+    let mut bema = parse(&source, path.parent().unwrap_or_else(|| Path::new(".")))?;
+    bema.source = Some(path.to_path_buf());
+    Ok(bema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(source: &str) -> Bema {
+        parse(source, Path::new(".")).unwrap()
+    }
+
+    #[test]
+    fn front_matter() {
+        let (fields, taken) = parse_front_matter("---\ntitle: Talk\nauthor:  Someone \n---\n# Slide\n");
+        assert_eq!(fields, vec![("title".to_string(), "Talk".to_string()), ("author".to_string(), "Someone".to_string())]);
+        assert_eq!(taken, 4);
+    }
+
+    #[test]
+    fn front_matter_is_only_at_the_start() {
+        assert_eq!(parse_front_matter("# Slide\n---\ntitle: Talk\n---\n"), (vec![], 0));
+        // unknown keys mean the first `---` separates slides
+        assert_eq!(parse_front_matter("---\n# Slide\n---\n"), (vec![], 0));
+        // an unclosed block is not front matter
+        assert_eq!(parse_front_matter("---\ntitle: Talk\n"), (vec![], 0));
+    }
+
+    #[test]
+    fn metadata_and_chrome() {
+        let bema = parse_str("---\ntitle: Talk\ndate: today\nfooter: {title}\n---\n# One\n");
+        assert_eq!(bema.metadata.title, "Talk");
+        assert_eq!(bema.metadata.date, "today");
+        assert_eq!(bema.header, DEFAULT_HEADER);
+        assert_eq!(bema.footer, "{title}");
+        assert_eq!(bema.slides.len(), 1);
+        assert_eq!(bema.slides[0].title, "One");
+    }
+
+    #[test]
+    fn slides_are_separated_by_rules() {
+        let bema = parse_str("# One\ntext\n---\n# Two\n```rust\n---\n```\n");
+        assert_eq!(bema.slides.len(), 2);
+        assert!(matches!(&bema.slides[0].items[..], [SlideItem::Text { text }] if text == "text"));
+        assert!(matches!(&bema.slides[1].items[..], [SlideItem::Code { extension, source }] if extension == "rust" && source == "---\n"));
+    }
+
+    #[test]
+    fn title_attributes() {
+        assert!(matches!(parse_title("Intro {align=left valign=bottom}"), (title, Some(Align::Left), Some(VAlign::Bottom)) if title == "Intro"));
+        assert!(matches!(parse_title("Intro {valign=middle}"), (title, None, Some(VAlign::Middle)) if title == "Intro"));
+        assert!(matches!(parse_title("Sets {}"), (title, None, None) if title == "Sets {}"));
+        assert!(matches!(parse_title("Intro {align=sideways}"), (title, None, None) if title == "Intro {align=sideways}"));
+        assert!(matches!(parse_title("Maps {a=b}"), (title, None, None) if title == "Maps {a=b}"));
+    }
+
+    #[test]
+    fn empty_slides_are_dropped_unless_they_have_notes() {
+        let bema = parse_str("---\n\n---\n???\nsay hello\n---\n# Last\n");
+        assert_eq!(bema.slides.len(), 2);
+        assert_eq!(bema.slides[0].title, "");
+        assert_eq!(bema.slides[0].notes, "say hello");
+        assert_eq!(bema.slides[1].title, "Last");
+    }
+
+    #[test]
+    fn a_deck_without_slides_is_an_error() {
+        assert!(parse("", Path::new(".")).is_err());
+        assert!(parse("---\n\n---\n", Path::new(".")).is_err());
+        // front matter alone
+        assert!(parse("---\ntitle: Talk\n---\n", Path::new(".")).is_err());
+    }
+}
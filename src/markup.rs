@@ -0,0 +1,207 @@
+use crate::measure;
+
+// inline markup of text items:
+// **bold**, *italic*, __underline__, `code` and [coloured text]{red} or [coloured text]{#ff8800}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct SpanStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub code: bool,
+    pub color: Option<(u8, u8, u8)>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: SpanStyle,
+}
+
+pub fn parse_color(name: &str) -> Option<(u8, u8, u8)> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let component = |k: usize| u8::from_str_radix(hex.get(k..k + 2)?, 16).ok();
+        return Some((component(0)?, component(2)?, component(4)?));
+    }
+    match name {
+        "black" => Some((0, 0, 0)),
+        "white" => Some((255, 255, 255)),
+        "red" => Some((230, 40, 40)),
+        "green" => Some((40, 200, 60)),
+        "blue" => Some((60, 110, 240)),
+        "yellow" => Some((240, 220, 40)),
+        "orange" => Some((250, 150, 20)),
+        "magenta" => Some((220, 50, 220)),
+        "cyan" => Some((40, 210, 220)),
+        "gray" | "grey" => Some((150, 150, 150)),
+        _ => None,
+    }
+}
+
+fn starts_with(chars: &[char], at: usize, marker: &str) -> bool {
+    marker.chars().enumerate().all(|(k, c)| chars.get(at + k) == Some(&c))
+}
+
+fn find(chars: &[char], from: usize, marker: &str) -> Option<usize> {
+    (from..chars.len()).find(|&at| starts_with(chars, at, marker))
+}
+
+fn push(spans: &mut Vec<Span>, c: char, style: SpanStyle) {
+    match spans.last_mut() {
+        Some(span) if span.style == style => span.text.push(c),
+        _ => spans.push(Span { text: c.to_string(), style }),
+    }
+}
+
+// a `[text]{color}` span starting at `at`: the end of its text, its color and where it ends
+fn color_span(chars: &[char], at: usize) -> Option<(usize, (u8, u8, u8), usize)> {
+    let text_end = find(chars, at + 1, "]{")?;
+    let end = find(chars, text_end + 2, "}")?;
+    let name = chars[text_end + 2..end].iter().collect::<String>();
+    parse_color(name.trim()).map(|color| (text_end, color, end + 1))
+}
+
+// a span marker and the style flag it toggles
+type Toggle = (&'static str, fn(&mut SpanStyle) -> &mut bool);
+
+fn parse_spans(chars: &[char], mut style: SpanStyle, spans: &mut Vec<Span>) {
+    let mut at = 0;
+    while at < chars.len() {
+        let c = chars[at];
+        if c == '\\' && at + 1 < chars.len() {
+            push(spans, chars[at + 1], style);
+            at += 2;
+            continue;
+        }
+        if c == '`' {
+            if let Some(end) = find(chars, at + 1, "`") {
+                let code = SpanStyle { code: true, ..style };
+                for c in &chars[at + 1..end] {
+                    push(spans, *c, code);
+                }
+                at = end + 1;
+                continue;
+            }
+        }
+        if c == '[' {
+            if let Some((text_end, color, end)) = color_span(chars, at) {
+                parse_spans(&chars[at + 1..text_end], SpanStyle { color: Some(color), ..style }, spans);
+                at = end;
+                continue;
+            }
+        }
+        let toggles: [Toggle; 3] = [
+            ("**", |s| &mut s.bold),
+            ("__", |s| &mut s.underline),
+            ("*", |s| &mut s.italic),
+        ];
+        let toggle = toggles.iter().find(|(marker, _)| starts_with(chars, at, marker));
+        if let Some((marker, field)) = toggle {
+            let len = marker.len();
+            // `__` and `*` inside a word, as in `snake__case` or `a*b*c`, are not markup
+            let in_word = |k: Option<usize>| *marker != "**" && matches!(k.and_then(|k| chars.get(k)), Some(c) if c.is_alphanumeric());
+            let closes_at = |k: usize| !in_word(Some(k + len));
+            // a marker only opens a span when it is closed later on the line, so that `2 * 3` stays as is
+            let opens = chars.get(at + len).map(|c| !c.is_whitespace()).unwrap_or(false)
+                && !in_word(at.checked_sub(1))
+                && (at + len + 1..chars.len()).any(|k| starts_with(chars, k, marker) && closes_at(k));
+            let flag = field(&mut style);
+            if (*flag && closes_at(at)) || (!*flag && opens) {
+                *flag = !*flag;
+                at += len;
+                continue;
+            }
+        }
+        push(spans, c, style);
+        at += 1;
+    }
+}
+
+// the styled runs of each line of a text
+pub fn parse(text: &str) -> Vec<Vec<Span>> {
+    text.split('\n').map(|line| {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut spans = vec![];
+        parse_spans(&chars, SpanStyle::default(), &mut spans);
+        spans
+    }).collect()
+}
+
+// the text of a line without its markup
+pub fn plain(spans: &[Span]) -> String {
+    spans.iter().map(|span| span.text.as_str()).collect()
+}
+
+// a line split into lines at most `width` wide, see `measure::wrap`
+pub fn wrap(spans: &[Span], width: f64, measure: impl Fn(&str) -> f64) -> Vec<Vec<Span>> {
+    let pieces = spans.iter().map(|span| (span.style, span.text.clone())).collect::<Vec<_>>();
+    measure::wrap(&pieces, width, measure).into_iter()
+        .map(|line| line.into_iter().map(|(style, text)| Span { text, style }).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // each span of a one-line text as (text, bold, italic, underline, code)
+    fn spans(text: &str) -> Vec<(String, bool, bool, bool, bool)> {
+        parse(text).remove(0).into_iter()
+            .map(|span| (span.text, span.style.bold, span.style.italic, span.style.underline, span.style.code))
+            .collect()
+    }
+
+    fn plain_span(text: &str) -> (String, bool, bool, bool, bool) {
+        (text.to_string(), false, false, false, false)
+    }
+
+    #[test]
+    fn styles() {
+        assert_eq!(spans("a **b** *c* __d__ `e`"), vec![
+            plain_span("a "),
+            ("b".to_string(), true, false, false, false),
+            plain_span(" "),
+            ("c".to_string(), false, true, false, false),
+            plain_span(" "),
+            ("d".to_string(), false, false, true, false),
+            plain_span(" "),
+            ("e".to_string(), false, false, false, true),
+        ]);
+    }
+
+    #[test]
+    fn unclosed_markers_are_text() {
+        assert_eq!(spans("2 * 3 = 6"), vec![plain_span("2 * 3 = 6")]);
+        assert_eq!(spans("*open"), vec![plain_span("*open")]);
+    }
+
+    #[test]
+    fn markers_inside_words_are_text() {
+        assert_eq!(spans("snake__case__name"), vec![plain_span("snake__case__name")]);
+        assert_eq!(spans("a*b*c"), vec![plain_span("a*b*c")]);
+        assert_eq!(spans("call __init__ twice"), vec![
+            plain_span("call "),
+            ("init".to_string(), false, false, true, false),
+            plain_span(" twice"),
+        ]);
+        // the closing marker is the first one that ends a word
+        assert_eq!(spans("*a*b*"), vec![("a*b".to_string(), false, true, false, false)]);
+    }
+
+    #[test]
+    fn escapes_and_colors() {
+        assert_eq!(spans("\\*a\\*"), vec![plain_span("*a*")]);
+        let line = parse("[x]{red} [y]{nope}").remove(0);
+        assert_eq!(line[0].text, "x");
+        assert_eq!(line[0].style.color, Some((230, 40, 40)));
+        assert_eq!(plain(&line[1..]), " [y]{nope}");
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(parse("a\n\nb").len(), 3);
+    }
+}
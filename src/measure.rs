@@ -0,0 +1,215 @@
+use crate::bema::{Align, VAlign, CodeOverflow};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// terminal cells taken by some text: two for wide characters such as CJK or most emoji,
+// none for combining marks
+pub fn columns(text: &str) -> usize {
+    text.width()
+}
+
+pub fn char_columns(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+// offset of something aligned in a space `room` larger than it
+pub fn offset(align: Align, room: f64) -> f64 {
+    let room = room.max(0.0);
+    match align {
+        Align::Left => 0.0,
+        Align::Center => room / 2.0,
+        Align::Right => room,
+    }
+}
+
+pub fn offset_vertical(valign: VAlign, room: f64) -> f64 {
+    let room = room.max(0.0);
+    match valign {
+        VAlign::Top => 0.0,
+        VAlign::Middle => room / 2.0,
+        VAlign::Bottom => room,
+    }
+}
+
+// left offset aligning lines as a block in `width`, 0 when one of them does not fit;
+// `measure` gives the width of a line in the same unit as `width`
+pub fn justify<T: AsRef<str>>(align: Align, width: f64, lines: &[T], measure: impl Fn(&str) -> f64) -> f64 {
+    let widest = lines.iter().map(|line| measure(line.as_ref())).fold(0.0, f64::max);
+    offset(align, width - widest)
+}
+
+// `justify` in terminal cells
+pub fn justify_columns<T: AsRef<str>>(align: Align, width: usize, lines: &[T]) -> usize {
+    let widest = lines.iter().map(|line| columns(line.as_ref())).max().unwrap_or(0);
+    offset(align, width.saturating_sub(widest) as f64) as usize
+}
+
+fn push_piece<T: Clone + PartialEq>(line: &mut Vec<(T, String)>, style: &T, text: &str) {
+    match line.last_mut() {
+        Some((last, last_text)) if last == style => last_text.push_str(text),
+        _ => line.push((style.clone(), text.to_string())),
+    }
+}
+
+fn trim_end<T>(line: &mut Vec<(T, String)>) {
+    while let Some((_, text)) = line.last_mut() {
+        let trimmed = text.trim_end().len();
+        text.truncate(trimmed);
+        if !text.is_empty() {
+            break;
+        }
+        line.pop();
+    }
+}
+
+// splits a line of styled pieces into lines at most `width` wide, breaking between words;
+// spaces at a break are dropped and a word wider than a whole line is broken where it overflows
+pub fn wrap<T: Clone + PartialEq>(pieces: &[(T, String)], width: f64, measure: impl Fn(&str) -> f64) -> Vec<Vec<(T, String)>> {
+    // words keep the spaces before them, so that a break can drop them
+    let mut words: Vec<Vec<(T, String)>> = vec![];
+    let mut in_space = true;
+    for (style, text) in pieces {
+        for c in text.chars() {
+            let space = c.is_whitespace();
+            if space && !in_space || words.is_empty() {
+                words.push(vec![]);
+            }
+            in_space = space;
+            push_piece(words.last_mut().unwrap(), style, c.encode_utf8(&mut [0; 4]));
+        }
+    }
+
+    let mut lines = vec![vec![]];
+    let mut used = 0.0;
+    for word in words {
+        let word_width: f64 = word.iter().map(|(_, text)| measure(text)).sum();
+        if used + word_width <= width {
+            for (style, text) in &word {
+                push_piece(lines.last_mut().unwrap(), style, text);
+            }
+            used += word_width;
+            continue;
+        }
+        // trailing spaces that do not fit are dropped rather than starting an empty line
+        if word.iter().all(|(_, text)| text.trim().is_empty()) {
+            continue;
+        }
+        // the word goes on a new line without its leading spaces, cut into as many lines as needed
+        if used > 0.0 {
+            trim_end(lines.last_mut().unwrap());
+            lines.push(vec![]);
+            used = 0.0;
+        }
+        for (style, text) in &word {
+            for c in text.chars() {
+                let c = c.encode_utf8(&mut [0; 4]).to_string();
+                if used == 0.0 && c.trim().is_empty() {
+                    continue;
+                }
+                let w = measure(&c);
+                if used + w > width && used > 0.0 {
+                    lines.push(vec![]);
+                    used = 0.0;
+                }
+                push_piece(lines.last_mut().unwrap(), style, &c);
+                used += w;
+            }
+        }
+    }
+    trim_end(lines.last_mut().unwrap());
+    lines
+}
+
+// a code line wider than `width`: wrapped with a marker starting each continuation, or cut with an ellipsis
+pub fn fit_code<T: Clone + PartialEq>(pieces: &[(T, String)], width: f64, overflow: CodeOverflow, measure: impl Fn(&str) -> f64) -> Vec<Vec<(T, String)>> {
+    let total: f64 = pieces.iter().map(|(_, text)| measure(text)).sum();
+    if overflow == CodeOverflow::Overflow || total <= width {
+        return vec![pieces.to_vec()];
+    }
+    let (marker, ellipsis) = ("↪ ", "…");
+    let mut lines = vec![vec![]];
+    let mut used = 0.0;
+    for (style, text) in pieces {
+        for c in text.chars() {
+            let c = c.encode_utf8(&mut [0; 4]).to_string();
+            let w = measure(&c);
+            match overflow {
+                CodeOverflow::Truncate if used + w > width - measure(ellipsis) => {
+                    push_piece(lines.last_mut().unwrap(), style, ellipsis);
+                    return lines;
+                },
+                CodeOverflow::Wrap if used + w > width && used > 0.0 => {
+                    lines.push(vec![]);
+                    push_piece(lines.last_mut().unwrap(), style, marker);
+                    used = measure(marker);
+                },
+                _ => {},
+            }
+            push_piece(lines.last_mut().unwrap(), style, &c);
+            used += w;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> f64 {
+        text.chars().count() as f64
+    }
+
+    fn wrapped(text: &str, width: f64) -> Vec<String> {
+        wrap(&[(0, text.to_string())], width, chars).iter()
+            .map(|line| line.iter().map(|(_, text)| text.as_str()).collect())
+            .collect()
+    }
+
+    fn pieces(pieces: &[(u8, &str)]) -> Vec<(u8, String)> {
+        pieces.iter().map(|(style, text)| (*style, text.to_string())).collect()
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        assert_eq!(wrapped("ab cd ef", 5.0), vec!["ab cd", "ef"]);
+        assert_eq!(wrapped("ab cd", 10.0), vec!["ab cd"]);
+        assert_eq!(wrapped("", 10.0), vec![""]);
+    }
+
+    #[test]
+    fn wraps_without_room() {
+        assert_eq!(wrapped("ab c", 0.0), vec!["a", "b", "c"]);
+        assert_eq!(wrapped("ab c", -3.0), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn breaks_long_words() {
+        assert_eq!(wrapped("abcdef", 4.0), vec!["abcd", "ef"]);
+        assert_eq!(wrapped("x abcdef", 4.0), vec!["x", "abcd", "ef"]);
+    }
+
+    #[test]
+    fn keeps_indentation_and_drops_spaces_at_breaks() {
+        assert_eq!(wrapped("  ab cd", 10.0), vec!["  ab cd"]);
+        assert_eq!(wrapped("ab   cd", 4.0), vec!["ab", "cd"]);
+        assert_eq!(wrapped("ab   ", 2.0), vec!["ab"]);
+    }
+
+    #[test]
+    fn wraps_styled_pieces() {
+        assert_eq!(wrap(&pieces(&[(1, "ab "), (2, "cd")]), 3.0, chars), vec![pieces(&[(1, "ab")]), pieces(&[(2, "cd")])]);
+        assert_eq!(wrap(&pieces(&[(1, "a"), (2, "b")]), 3.0, chars), vec![pieces(&[(1, "a"), (2, "b")])]);
+    }
+
+    #[test]
+    fn fits_code() {
+        let code = pieces(&[(1, "abc"), (2, "def")]);
+        assert_eq!(fit_code(&code, 4.0, CodeOverflow::Overflow, chars), vec![code.clone()]);
+        assert_eq!(fit_code(&code, 6.0, CodeOverflow::Truncate, chars), vec![code.clone()]);
+        // the marker and the ellipsis take the style of the character where the line is cut
+        assert_eq!(fit_code(&code, 4.0, CodeOverflow::Wrap, chars), vec![pieces(&[(1, "abc"), (2, "d")]), pieces(&[(2, "↪ ef")])]);
+        assert_eq!(fit_code(&code, 4.0, CodeOverflow::Truncate, chars), vec![pieces(&[(1, "abc"), (2, "…")])]);
+        assert_eq!(fit_code(&code, 3.0, CodeOverflow::Truncate, chars), vec![pieces(&[(1, "ab…")])]);
+        assert_eq!(fit_code(&code, 0.0, CodeOverflow::Truncate, chars), vec![pieces(&[(1, "…")])]);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::bema::{Bema, Slide, SlideItem};
+use crate::markup;
+use std::collections::HashMap;
+
+// keys that drive navigation, independent of the frontend
+pub enum Key {
+    Char(char),
+    Enter,
+    Escape,
+    Backspace,
+}
+
+pub enum Navigation {
+    // the key is left to the frontend's own bindings
+    Ignored,
+    Consumed,
+    Goto(usize),
+}
+
+struct Search {
+    query: String,
+    // slide the search started from, where escape goes back to
+    origin: usize,
+}
+
+// vim-like navigation: `12` then enter or `12G`, `/` incremental search over titles and text
+// repeated with `n` and `N`, `m<letter>` to set a mark and `'<letter>` to jump to it
+pub struct Navigator {
+    count: String,
+    search: Option<Search>,
+    last_search: String,
+    // `m` or `'` waiting for the mark letter
+    pending_mark: Option<char>,
+    marks: HashMap<char, usize>,
+}
+
+fn collect_text(items: &[SlideItem], text: &mut String) {
+    for item in items {
+        match item {
+            SlideItem::Text { text: t } => {
+                for spans in markup::parse(t) {
+                    text.push('\n');
+                    text.push_str(&markup::plain(&spans));
+                }
+            },
+            SlideItem::Cols { items } | SlideItem::Rows { items } | SlideItem::Framed { items } | SlideItem::Aligned { items, .. } => collect_text(items, text),
+            _ => {},
+        }
+    }
+}
+
+fn matches(slide: &Slide, query: &str) -> bool {
+    let mut text = slide.title.clone();
+    collect_text(&slide.items, &mut text);
+    text.to_lowercase().contains(&query.to_lowercase())
+}
+
+// the first slide matching `query`, going through the deck from `from` in `direction` and wrapping around
+fn find(bema: &Bema, query: &str, from: usize, direction: isize) -> Option<usize> {
+    let len = bema.slides.len() as isize;
+    (0..len)
+        .map(|k| (from as isize + direction * k).rem_euclid(len) as usize)
+        .find(|&k| matches(&bema.slides[k], query))
+}
+
+impl Navigator {
+    pub fn new() -> Navigator {
+        Navigator { count: String::new(), search: None, last_search: String::new(), pending_mark: None, marks: HashMap::new() }
+    }
+
+    // while typing a search, a count or a mark, every key belongs to the navigator
+    pub fn is_active(&self) -> bool {
+        self.search.is_some() || self.pending_mark.is_some() || !self.count.is_empty()
+    }
+
+    // what is being typed, shown at the bottom of the slide
+    pub fn status(&self) -> Option<String> {
+        if let Some(search) = &self.search {
+            Some(format!("/{}", search.query))
+        } else if let Some(mark) = self.pending_mark {
+            Some(mark.to_string())
+        } else if !self.count.is_empty() {
+            Some(self.count.clone())
+        } else {
+            None
+        }
+    }
+
+    fn goto_number(&mut self, bema: &Bema) -> Navigation {
+        let n = self.count.parse::<usize>().unwrap_or(0);
+        self.count.clear();
+        if n >= 1 && n <= bema.slides.len() { Navigation::Goto(n - 1) } else { Navigation::Consumed }
+    }
+
+    fn search_key(&mut self, bema: &Bema, key: Key) -> Navigation {
+        let search = self.search.as_mut().unwrap();
+        match key {
+            Key::Enter => {
+                self.last_search = search.query.clone();
+                self.search = None;
+                Navigation::Consumed
+            },
+            Key::Escape => {
+                let origin = search.origin;
+                self.search = None;
+                Navigation::Goto(origin)
+            },
+            Key::Backspace => {
+                search.query.pop();
+                match find(bema, &search.query, search.origin, 1) {
+                    Some(k) if !search.query.is_empty() => Navigation::Goto(k),
+                    _ => Navigation::Goto(search.origin),
+                }
+            },
+            Key::Char(c) => {
+                search.query.push(c);
+                find(bema, &search.query, search.origin, 1).map(Navigation::Goto).unwrap_or(Navigation::Consumed)
+            },
+        }
+    }
+
+    pub fn key(&mut self, bema: &Bema, current: usize, key: Key) -> Navigation {
+        if self.search.is_some() {
+            return self.search_key(bema, key);
+        }
+        if let Some(mark) = self.pending_mark.take() {
+            if let Key::Char(c) = key {
+                if mark == 'm' {
+                    self.marks.insert(c, current);
+                } else if let Some(&k) = self.marks.get(&c) {
+                    return Navigation::Goto(k.min(bema.slides.len() - 1));
+                }
+            }
+            return Navigation::Consumed;
+        }
+        match key {
+            Key::Char(c) if c.is_ascii_digit() && (c != '0' || !self.count.is_empty()) => {
+                self.count.push(c);
+                Navigation::Consumed
+            },
+            Key::Enter | Key::Char('G') if !self.count.is_empty() => self.goto_number(bema),
+            Key::Char('/') => {
+                self.count.clear();
+                self.search = Some(Search { query: String::new(), origin: current });
+                Navigation::Consumed
+            },
+            Key::Char(c) if c == 'm' || c == '\'' => {
+                self.count.clear();
+                self.pending_mark = Some(c);
+                Navigation::Consumed
+            },
+            Key::Char(c) if (c == 'n' || c == 'N') && !self.last_search.is_empty() => {
+                let direction = if c == 'n' { 1 } else { -1 };
+                let from = (current as isize + direction).rem_euclid(bema.slides.len() as isize) as usize;
+                find(bema, &self.last_search, from, direction).map(Navigation::Goto).unwrap_or(Navigation::Consumed)
+            },
+            Key::Escape if !self.count.is_empty() || !self.last_search.is_empty() => {
+                // escape forgets the search, giving `n` back its usual meaning
+                self.count.clear();
+                self.last_search.clear();
+                Navigation::Consumed
+            },
+            _ => {
+                self.count.clear();
+                Navigation::Ignored
+            },
+        }
+    }
+}
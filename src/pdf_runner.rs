@@ -0,0 +1,385 @@
+use crate::runner::{Runner, chrome, Highlighting, load_highlighting, output_path, decode_image, image_layout, fit_image, table_widths, align_row};
+use crate::bema::{Bema, Slide, SlideItem, Layout, Align, VAlign, CodeOverflow};
+use crate::markup;
+use crate::measure;
+use crate::theme::Theme;
+use std::collections::hash_map::{HashMap, Entry};
+use std::fs::File;
+use std::io::{BufWriter, Cursor};
+use std::path::PathBuf;
+use crate::error::Result;
+
+use printpdf::{PdfDocument, PdfLayerReference, IndirectFontRef, Mm, Point, Line, Color, Rgb, Image};
+use image::{DynamicImage, GenericImageView, Rgba};
+
+const PAGE_WIDTH: f64 = 254.0;
+const PAGE_HEIGHT: f64 = 142.875;
+const MARGIN: f64 = 10.0;
+const INDEX_SIZE: f64 = 8.0;
+const TITLE_SIZE: f64 = 28.0;
+const TEXT_SIZE: f64 = 16.0;
+const CODE_SIZE: f64 = 14.0;
+const SUBTITLE_SIZE: f64 = 20.0;
+const FRAME_PADDING: f64 = 2.0;
+// images are laid out as if the slide was 1280 pixels wide, like a gui window
+const IMAGE_DPI: f64 = 1280.0 / (PAGE_WIDTH / 25.4);
+
+pub struct PdfRunner {
+    pub output: Option<PathBuf>,
+}
+
+struct Page<'a> {
+    layer: PdfLayerReference,
+    font: &'a IndirectFontRef,
+    highlighting: &'a Highlighting,
+    theme: &'a Theme,
+    code_overflow: CodeOverflow,
+    // how far down items can go
+    bottom: f64,
+    images: &'a Images,
+}
+
+// the decoded images of the deck, keyed by the address of their bytes since both measuring and
+// drawing an image need it
+type Images = HashMap<usize, DynamicImage>;
+
+fn decode_images(items: &[SlideItem], images: &mut Images) -> Result<()> {
+    for item in items {
+        match item {
+            SlideItem::Image { image, extension, .. } => {
+                if let Entry::Vacant(entry) = images.entry(image.as_ptr() as usize) {
+                    entry.insert(decode_image(image, extension)?);
+                }
+            },
+            SlideItem::Cols { items } | SlideItem::Rows { items } | SlideItem::Framed { items } | SlideItem::Aligned { items, .. } => decode_images(items, images)?,
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+fn pt_to_mm(pt: f64) -> f64 {
+    pt * 25.4 / 72.0
+}
+
+// the embedded font is monospace, each glyph is about half as wide as it is high
+fn text_width(text: &str, size: f64) -> f64 {
+    measure::columns(text) as f64 * pt_to_mm(size) / 2.0
+}
+
+fn justify<T: AsRef<str>>(align: Align, width: f64, texts: &[T], size: f64) -> f64 {
+    measure::justify(align, width, texts, |text| text_width(text, size))
+}
+
+// the lines of a title slide below its title, with their size and grey level
+fn below_title(slide: &Slide) -> Vec<(&str, f64, u8)> {
+    let mut lines = vec![];
+    if let Layout::Title { subtitle, byline } = &slide.layout {
+        lines.extend(subtitle.lines().map(|line| (line, SUBTITLE_SIZE, 0)));
+        if !byline.is_empty() {
+            lines.push(("", TEXT_SIZE, 128));
+        }
+        lines.extend(byline.lines().map(|line| (line, TEXT_SIZE, 128)));
+    }
+    lines
+}
+
+// the height of a slide title and of the lines below it
+fn title_height(slide: &Slide) -> f64 {
+    2.0 * pt_to_mm(TITLE_SIZE) + below_title(slide).iter().map(|(_, size, _)| pt_to_mm(*size) * 1.2).sum::<f64>()
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::Rgb(Rgb::new(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0, None))
+}
+
+fn flatten_alpha(img: &DynamicImage) -> DynamicImage {
+    let mut flat = image::RgbImage::new(img.width(), img.height());
+    for (x, y, Rgba([r, g, b, a])) in img.pixels() {
+        let blend = |c: u8| ((c as u16 * a as u16 + 255 * (255 - a as u16)) / 255) as u8;
+        flat.put_pixel(x, y, image::Rgb([blend(r), blend(g), blend(b)]));
+    }
+    DynamicImage::ImageRgb8(flat)
+}
+
+impl PdfRunner {
+    fn write_line(&self, page: &Page, text: &str, size: f64, x: f64, y: f64) {
+        page.layer.use_text(text, size, Mm(x), Mm(PAGE_HEIGHT - y - pt_to_mm(size)), page.font);
+    }
+
+    fn draw_rectangle(&self, page: &Page, x: f64, y: f64, width: f64, height: f64) {
+        let points = vec![
+            (Point::new(Mm(x), Mm(PAGE_HEIGHT - y)), false),
+            (Point::new(Mm(x + width), Mm(PAGE_HEIGHT - y)), false),
+            (Point::new(Mm(x + width), Mm(PAGE_HEIGHT - y - height)), false),
+            (Point::new(Mm(x), Mm(PAGE_HEIGHT - y - height)), false),
+        ];
+        page.layer.set_outline_color(rgb(0, 0, 0));
+        page.layer.set_outline_thickness(1.0);
+        page.layer.add_shape(Line { points, is_closed: true, has_fill: false, has_stroke: true, is_clipping_path: false });
+    }
+
+    fn draw_underline(&self, page: &Page, x: f64, y: f64, width: f64) {
+        let points = vec![
+            (Point::new(Mm(x), Mm(PAGE_HEIGHT - y)), false),
+            (Point::new(Mm(x + width), Mm(PAGE_HEIGHT - y)), false),
+        ];
+        page.layer.set_outline_thickness(0.5);
+        page.layer.add_shape(Line { points, is_closed: false, has_fill: false, has_stroke: true, is_clipping_path: false });
+    }
+
+    // the index, footer and title of a slide, the title `top` lower than usual, returning where its items start
+    fn draw_chrome(&self, page: &Page, bema: &Bema, i: usize, top: f64) -> f64 {
+        let slide = &bema.slides[i];
+        let width = PAGE_WIDTH - 2.0 * MARGIN;
+        let mut y = MARGIN / 2.0;
+        page.layer.set_fill_color(rgb(128, 128, 128));
+        self.write_line(page, &chrome(&bema.header, bema, i), INDEX_SIZE, MARGIN / 2.0, y);
+        let footer = chrome(&bema.footer, bema, i);
+        if !footer.is_empty() {
+            self.write_line(page, &footer, INDEX_SIZE, MARGIN + justify(Align::Center, width, &[&footer], INDEX_SIZE), PAGE_HEIGHT - MARGIN / 2.0 - pt_to_mm(INDEX_SIZE));
+        }
+        page.layer.set_fill_color(rgb(0, 0, 0));
+        y += pt_to_mm(INDEX_SIZE) + MARGIN / 2.0 + top;
+        self.write_line(page, &slide.title, TITLE_SIZE, MARGIN + justify(slide.align, width, &[&slide.title], TITLE_SIZE), y);
+        y += 1.2 * pt_to_mm(TITLE_SIZE);
+        for (line, size, grey) in below_title(slide) {
+            page.layer.set_fill_color(rgb(grey, grey, grey));
+            self.write_line(page, line, size, MARGIN + justify(slide.align, width, &[line], size), y);
+            y += pt_to_mm(size) * 1.2;
+        }
+        page.layer.set_fill_color(rgb(0, 0, 0));
+        y + 0.8 * pt_to_mm(TITLE_SIZE)
+    }
+
+    // how far down `render_item` would move, images being measured against the room of a whole page
+    fn item_height(&self, page: &Page, item: &SlideItem, width: f64) -> Result<f64> {
+        Ok(match item {
+            SlideItem::Image { image, size, .. } => {
+                let mm_per_px = 25.4 / IMAGE_DPI;
+                let img = &page.images[&(image.as_ptr() as usize)];
+                let top = MARGIN + pt_to_mm(INDEX_SIZE) + 2.0 * pt_to_mm(TITLE_SIZE);
+                let area = (width / mm_per_px, (page.bottom - top) / mm_per_px);
+                // render_item shrinks images to the room left, so they never need more than a page
+                image_layout(size, (img.width() as f64, img.height() as f64), area).height.min(area.1) * mm_per_px
+            },
+            SlideItem::Code { extension, source } => {
+                let lines = page.highlighting.highlight(extension, source, page.theme).iter()
+                    .map(|ranges| measure::fit_code(ranges, width, page.code_overflow, |t| text_width(t, CODE_SIZE)).len())
+                    .sum::<usize>();
+                lines as f64 * pt_to_mm(CODE_SIZE) * 1.2
+            },
+            SlideItem::Text { text } => {
+                let lines = markup::parse(text).iter()
+                    .map(|spans| markup::wrap(spans, width, |t| text_width(t, TEXT_SIZE)).len())
+                    .sum::<usize>();
+                lines as f64 * pt_to_mm(TEXT_SIZE) * 1.2
+            },
+            SlideItem::Cols { items } => {
+                let w = width / items.len() as f64;
+                let mut height: f64 = 0.0;
+                for item2 in items {
+                    height = height.max(self.item_height(page, item2, w)?);
+                }
+                height
+            },
+            SlideItem::Rows { items } | SlideItem::Aligned { items, .. } => {
+                let mut height = 0.0;
+                for item2 in items {
+                    height += self.item_height(page, item2, width)?;
+                }
+                height
+            },
+            SlideItem::Framed { items } => {
+                let mut height = 2.0 * FRAME_PADDING;
+                for item2 in items {
+                    height += self.item_height(page, item2, width - 2.0 * FRAME_PADDING)?;
+                }
+                height
+            },
+            SlideItem::Table { header, rows, .. } => {
+                (rows.len() + if header.is_empty() { 0 } else { 1 }) as f64 * pt_to_mm(TEXT_SIZE) * 1.4
+            },
+            SlideItem::Pause => 0.0,
+        })
+    }
+
+    fn render_item(&self, page: &Page, item: &SlideItem, align: Align, x: f64, y: &mut f64, width: f64) -> Result<()> {
+        match item {
+            SlideItem::Image { image, size, .. } => {
+                let mm_per_px = 25.4 / IMAGE_DPI;
+                let room = (page.bottom - *y).max(0.0);
+                let img = fit_image(&page.images[&(image.as_ptr() as usize)], size, (width / mm_per_px, room / mm_per_px));
+                let (w, h) = (img.width() as f64 * mm_per_px, img.height() as f64 * mm_per_px);
+                let factor = 1.0f64.min(width / w).min(room / h);
+                let (w, h) = (w * factor, h * factor);
+                Image::from_dynamic_image(&flatten_alpha(&img)).add_to_layer(page.layer.clone(),
+                    Some(Mm(x + measure::offset(align, width - w))), Some(Mm(PAGE_HEIGHT - *y - h)),
+                    None, Some(factor), Some(factor), Some(IMAGE_DPI));
+                *y += h;
+            },
+            SlideItem::Code { extension, source } => {
+                let lines = page.highlighting.highlight(extension, source, page.theme).iter()
+                    .flat_map(|ranges| measure::fit_code(ranges, width, page.code_overflow, |t| text_width(t, CODE_SIZE)))
+                    .collect::<Vec<_>>();
+                let plains = lines.iter().map(|ranges| ranges.iter().map(|(_, text)| text.as_str()).collect::<String>()).collect::<Vec<_>>();
+                let x0 = x + justify(align, width, &plains, CODE_SIZE);
+                for ranges in lines {
+                    let mut dx = 0.0;
+                    for (style, text) in ranges {
+                        let c = style.foreground;
+                        page.layer.set_fill_color(rgb(c.r, c.g, c.b));
+                        self.write_line(page, &text, CODE_SIZE, x0 + dx, *y);
+                        dx += text_width(&text, CODE_SIZE);
+                    }
+                    *y += pt_to_mm(CODE_SIZE) * 1.2;
+                }
+                page.layer.set_fill_color(rgb(0, 0, 0));
+            },
+            SlideItem::Text { text } => {
+                let lines = markup::parse(text).iter()
+                    .flat_map(|spans| markup::wrap(spans, width, |t| text_width(t, TEXT_SIZE)))
+                    .collect::<Vec<_>>();
+                let plains = lines.iter().map(|spans| markup::plain(spans)).collect::<Vec<_>>();
+                let x0 = x + justify(align, width, &plains, TEXT_SIZE);
+                for spans in lines {
+                    let mut dx = 0.0;
+                    for span in spans {
+                        let style = span.style;
+                        let w = text_width(&span.text, TEXT_SIZE);
+                        let (r, g, b) = style.color.unwrap_or(if style.code { (90, 90, 90) } else { (0, 0, 0) });
+                        page.layer.set_fill_color(rgb(r, g, b));
+                        self.write_line(page, &span.text, TEXT_SIZE, x0 + dx, *y);
+                        // the embedded font has a single face, bold is overprinted slightly shifted
+                        if style.bold {
+                            self.write_line(page, &span.text, TEXT_SIZE, x0 + dx + 0.15, *y);
+                        }
+                        if style.underline {
+                            page.layer.set_outline_color(rgb(r, g, b));
+                            self.draw_underline(page, x0 + dx, *y + pt_to_mm(TEXT_SIZE) * 1.05, w);
+                        }
+                        dx += w;
+                    }
+                    page.layer.set_fill_color(rgb(0, 0, 0));
+                    *y += pt_to_mm(TEXT_SIZE) * 1.2;
+                }
+            },
+            SlideItem::Cols { items } => {
+                let w = width / items.len() as f64;
+                let mut ys = vec![];
+                for (pos, item2) in items.iter().enumerate() {
+                    let mut y2 = *y;
+                    self.render_item(page, item2, align, x + w * pos as f64, &mut y2, w)?;
+                    ys.push(y2);
+                }
+                *y = ys.iter().cloned().fold(*y, f64::max);
+            },
+            SlideItem::Rows { items } => {
+                for item2 in items {
+                    self.render_item(page, item2, align, x, y, width)?;
+                }
+            },
+            SlideItem::Framed { items } => {
+                let y0 = *y;
+                *y += FRAME_PADDING;
+                for item2 in items {
+                    self.render_item(page, item2, align, x + FRAME_PADDING, y, width - 2.0 * FRAME_PADDING)?;
+                }
+                *y += FRAME_PADDING;
+                self.draw_rectangle(page, x, y0, width, *y - y0);
+            },
+            SlideItem::Aligned { align, items } => {
+                for item2 in items {
+                    self.render_item(page, item2, *align, x, y, width)?;
+                }
+            },
+            SlideItem::Table { header, rows, align: column_align } => {
+                let widths = table_widths(header, rows);
+                let char_width = text_width(" ", TEXT_SIZE);
+                let line_height = pt_to_mm(TEXT_SIZE) * 1.4;
+                let table_width = widths.iter().map(|w| (w + 2) as f64 * char_width).sum::<f64>();
+                let x0 = x + measure::offset(align, width - table_width);
+                let mut lines = vec![];
+                if !header.is_empty() {
+                    lines.push(align_row(header, &widths, column_align));
+                }
+                lines.extend(rows.iter().map(|row| align_row(row, &widths, column_align)));
+                for line in lines {
+                    let mut cell_x = x0;
+                    for (cell, w) in line.iter().zip(widths.iter()) {
+                        let cell_width = (w + 2) as f64 * char_width;
+                        self.write_line(page, cell, TEXT_SIZE, cell_x + char_width, *y + pt_to_mm(TEXT_SIZE) * 0.2);
+                        self.draw_rectangle(page, cell_x, *y, cell_width, line_height);
+                        cell_x += cell_width;
+                    }
+                    *y += line_height;
+                }
+            },
+            SlideItem::Pause => {},
+        }
+        Ok(())
+    }
+}
+
+impl Runner for PdfRunner {
+    fn run(&self, bema: &Bema) -> Result<()> {
+        let highlighting = load_highlighting(bema)?;
+        let mut images = Images::new();
+        for slide in &bema.slides {
+            decode_images(&slide.items, &mut images)?;
+        }
+        // pages are meant to be printed, code is always highlighted for a white background
+        let theme = &Theme::light();
+
+        // printpdf 0.3 only writes the title to the document info, the other metadata is left to the header and footer
+        let (doc, first_page, first_layer) = PdfDocument::new(if bema.metadata.title.is_empty() { "bema" } else { &bema.metadata.title }, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "slide 1");
+        let font = doc.add_external_font(Cursor::new(&include_bytes!("3270 Narrow Nerd Font Complete.ttf")[..]))?;
+
+        let mut first = Some((first_page, first_layer));
+        let mut add_page = |name: String| {
+            let (page_index, layer_index) = first.take().unwrap_or_else(|| doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), name));
+            doc.get_page(page_index).get_layer(layer_index)
+        };
+        let width = PAGE_WIDTH - 2.0 * MARGIN;
+        for (i, slide) in bema.slides.iter().enumerate() {
+            let mut page = Page { layer: add_page(format!("slide {}", i + 1)), font: &font, highlighting: &highlighting, theme, code_overflow: bema.code_overflow,
+                bottom: PAGE_HEIGHT - MARGIN, images: &images };
+            if slide.layout == Layout::Image {
+                // without index, title or footer, images fill the whole page
+                page.bottom = PAGE_HEIGHT;
+                let mut y = 0.0;
+                for item in &slide.items {
+                    self.render_item(&page, item, slide.align, 0.0, &mut y, PAGE_WIDTH)?;
+                }
+                continue;
+            }
+            let mut heights = vec![];
+            for item in &slide.items {
+                heights.push(self.item_height(&page, item, width)?);
+            }
+            // a slide that fits on its page is placed in the room below the index
+            let room = page.bottom - MARGIN - pt_to_mm(INDEX_SIZE) - title_height(slide) - heights.iter().sum::<f64>();
+            let top = if slide.valign != VAlign::Top && room > 0.0 { measure::offset_vertical(slide.valign, room) } else { 0.0 };
+            let mut y = self.draw_chrome(&page, bema, i, top);
+            let (mut empty, mut overflowed) = (true, false);
+            // items that do not fit go on a continuation page with the same title
+            for (item, height) in slide.items.iter().zip(heights) {
+                if y + height > page.bottom && !empty {
+                    page.layer = add_page(format!("slide {} (continued)", i + 1));
+                    y = self.draw_chrome(&page, bema, i, 0.0);
+                }
+                empty = false;
+                if y + height > page.bottom && !overflowed {
+                    overflowed = true;
+                    eprintln!("warning: slide {} does not fit on a pdf page", i + 1);
+                }
+                self.render_item(&page, item, slide.align, MARGIN, &mut y, width)?;
+            }
+        }
+
+        let pdf_path = output_path(&self.output, "bema.pdf")?;
+        doc.save(&mut BufWriter::new(File::create(&pdf_path)?))?;
+        println!("export pdf: {}", pdf_path.display());
+        Ok(())
+    }
+}
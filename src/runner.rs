@@ -1,41 +1,303 @@
-use crate::bema::Bema;
+use crate::bema::{Bema, Align, SlideItem, Layout, HighlightSource, ImageSize, Metadata, DEFAULT_HEADER};
+use crate::markdown;
+use crate::measure;
+use crate::theme::Theme;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{ThemeSet, Theme as CodeTheme, Style};
+use syntect::parsing::{SyntaxSet, SyntaxDefinition, SyntaxReference};
+use syntect::util::LinesWithEndings;
 use image::io::Reader as ImageReader;
+use image::{DynamicImage, GenericImageView};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::rc::Rc;
+use std::path::PathBuf;
+use std::io::Cursor;
+use std::time::SystemTime;
 
-use crossterm::Result;
+use crate::error::Result;
 
 pub trait Runner {
     fn run(&self, bema: &Bema) -> Result<()>;
 }
 
-pub fn fit_image_bytes(bytes: &[u8], width: &Option<usize>, extension: &String) -> Vec<u8> {
-    let mut img = ImageReader::with_format(std::io::Cursor::new(bytes), image::ImageFormat::from_extension(extension.replace(".", "")).unwrap()).decode().unwrap();
-    img = width.map(|w| img.resize(w as u32, (w * 2) as u32, image::imageops::FilterType::Lanczos3)).unwrap_or(img);
+// images with an unknown extension are decoded according to their content
+pub fn decode_image(bytes: &[u8], extension: &str) -> Result<DynamicImage> {
+    let reader = match image::ImageFormat::from_extension(extension.replace(".", "")) {
+        Some(format) => ImageReader::with_format(Cursor::new(bytes), format),
+        None => ImageReader::new(Cursor::new(bytes)).with_guessed_format()?,
+    };
+    Ok(reader.decode()?)
+}
+
+// where an image of `image` pixels goes in an area of `area` pixels: the size it is drawn at,
+// and the part of it that is shown as x, y, width and height, all of it unless it is cropped to cover the area
+pub struct ImageLayout {
+    pub width: f64,
+    pub height: f64,
+    pub crop: (f64, f64, f64, f64),
+}
+
+pub fn image_layout(size: &ImageSize, image: (f64, f64), area: (f64, f64)) -> ImageLayout {
+    let (width, height) = (image.0.max(1.0), image.1.max(1.0));
+    let factor = match size {
+        ImageSize::Natural => 1.0,
+        ImageSize::Width(w) => *w as f64 / width,
+        ImageSize::Height(h) => *h as f64 / height,
+        ImageSize::WidthPercent(p) => area.0 * p / 100.0 / width,
+        ImageSize::HeightPercent(p) => area.1 * p / 100.0 / height,
+        ImageSize::Contain => (area.0 / width).min(area.1 / height),
+        ImageSize::Cover => {
+            let factor = (area.0 / width).max(area.1 / height);
+            let (shown_width, shown_height) = (area.0 / factor, area.1 / factor);
+            return ImageLayout {
+                width: area.0,
+                height: area.1,
+                crop: ((width - shown_width) / 2.0, (height - shown_height) / 2.0, shown_width, shown_height),
+            };
+        },
+    };
+    // images never overflow the width they are laid out in
+    let factor = factor.min(area.0 / width);
+    ImageLayout { width: width * factor, height: height * factor, crop: (0.0, 0.0, width, height) }
+}
+
+// `size` with its lengths, given for a 1280 pixels wide slide, in pixels of a slide `slide_width` pixels wide
+pub fn scale_image_size(size: &ImageSize, slide_width: f64) -> ImageSize {
+    let scale = |length: usize| (length as f64 * slide_width / 1280.0).round() as usize;
+    match *size {
+        ImageSize::Width(w) => ImageSize::Width(scale(w)),
+        ImageSize::Height(h) => ImageSize::Height(scale(h)),
+        size => size,
+    }
+}
+
+// the image cropped and resized as it is drawn in an area of `area` pixels
+pub fn fit_image(img: &DynamicImage, size: &ImageSize, area: (f64, f64)) -> DynamicImage {
+    let layout = image_layout(size, (img.width() as f64, img.height() as f64), area);
+    let (x, y, w, h) = layout.crop;
+    img.crop_imm(x as u32, y as u32, (w as u32).max(1), (h as u32).max(1))
+        .resize_exact((layout.width as u32).max(1), (layout.height as u32).max(1), image::imageops::FilterType::Lanczos3)
+}
+
+pub fn png_bytes(img: &DynamicImage) -> Result<Vec<u8>> {
     let mut bytes: Vec<u8> = Vec::new();
-    img.write_to(&mut bytes, image::ImageOutputFormat::Png).unwrap();
-    bytes
+    img.write_to(&mut bytes, image::ImageOutputFormat::Png)?;
+    Ok(bytes)
 }
 
 
-pub fn get_justify(size: usize, texts: Vec<&String>) -> Result<usize> {
+// the lines of a code block, each one split into styled pieces without the line ending
+pub type HighlightedCode = Rc<Vec<Vec<(Style, String)>>>;
 
-    let mut whitespaces : usize = size as usize;
+pub struct Highlighting {
+    pub syntaxes: SyntaxSet,
+    pub themes: ThemeSet,
+    // highlighted code blocks keyed by code theme, extension and source, so that drawing a frame does not highlight again
+    cache: RefCell<HashMap<(String, String, String), HighlightedCode>>,
+}
 
-    for text in texts {
-        let new_whitespaces = if text.len() < size as usize {
-            let x = (size as usize - text.len()) / 2;
-                x
-        } else {
-            0
-        };
-        if new_whitespaces == 0 {
-            whitespaces = 0;
-            break
-        } else if new_whitespaces < whitespaces {
-                whitespaces = new_whitespaces
+fn code_blocks<'a>(items: &'a [SlideItem], blocks: &mut Vec<(&'a String, &'a String)>) {
+    for item in items {
+        match item {
+            SlideItem::Code { extension, source } => blocks.push((extension, source)),
+            SlideItem::Cols { items } | SlideItem::Rows { items } | SlideItem::Framed { items } | SlideItem::Aligned { items, .. } => code_blocks(items, blocks),
+            _ => {},
+        }
+    }
+}
+
+// the default syntect grammars and themes, plus the ones registered on the deck
+pub fn load_highlighting(bema: &Bema) -> Result<Highlighting> {
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    let mut themes = ThemeSet::load_defaults();
+    for source in &bema.highlighting {
+        match source {
+            HighlightSource::Syntax(sublime_syntax) => {
+                builder.add(SyntaxDefinition::load_from_str(sublime_syntax, true, None)?);
+            },
+            HighlightSource::CodeTheme { name, tm_theme } => {
+                let theme = ThemeSet::load_from_reader(&mut Cursor::new(tm_theme))?;
+                themes.themes.insert(name.clone(), theme);
+            },
+            HighlightSource::Dir(path) => {
+                builder.add_from_folder(path, true)?;
+                themes.add_from_folder(path)?;
+            },
+        }
+    }
+    let highlighting = Highlighting { syntaxes: builder.build(), themes, cache: RefCell::new(HashMap::new()) };
+    // every block of the deck is highlighted up front with both themes, blocks added by a reload are highlighted when first drawn
+    let mut blocks = vec![];
+    for slide in &bema.slides {
+        code_blocks(&slide.items, &mut blocks);
+    }
+    for (extension, source) in blocks {
+        highlighting.highlight(extension, source, &bema.theme);
+        highlighting.highlight(extension, source, &bema.alternate_theme);
+    }
+    Ok(highlighting)
+}
+
+impl Highlighting {
+    // unknown extensions are rendered as plain text
+    pub fn syntax(&self, extension: &str) -> &SyntaxReference {
+        self.syntaxes.find_syntax_by_token(extension.trim_start_matches('.'))
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text())
+    }
+
+    // the syntect theme a deck theme highlights code with
+    pub fn theme(&self, theme: &Theme) -> &CodeTheme {
+        self.themes.themes.get(&theme.code_theme).unwrap_or(&self.themes.themes["base16-ocean.dark"])
+    }
+
+    pub fn highlight(&self, extension: &str, source: &str, theme: &Theme) -> HighlightedCode {
+        let key = (theme.code_theme.clone(), extension.to_string(), source.to_string());
+        if let Some(code) = self.cache.borrow().get(&key) {
+            return code.clone();
         }
+        let mut h = HighlightLines::new(self.syntax(extension), self.theme(theme));
+        let code: HighlightedCode = Rc::new(LinesWithEndings::from(source).map(|line| {
+            h.highlight(line, &self.syntaxes).into_iter()
+                .map(|(style, text)| (style, text.trim_end_matches(['\n', '\r']).to_string()))
+                .collect()
+        }).collect());
+        self.cache.borrow_mut().insert(key, code.clone());
+        code
+    }
+}
+
+// where an exporter writes a file, creating the output directory if needed
+pub fn output_path(output: &Option<PathBuf>, file_name: &str) -> Result<PathBuf> {
+    match output {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            Ok(dir.join(file_name))
+        },
+        None => Ok(PathBuf::from(file_name)),
     }
+}
+
+pub fn source_modified(bema: &Bema) -> Option<SystemTime> {
+    bema.source.as_ref()
+        .and_then(|path| fs::metadata(path).ok())
+        .and_then(|metadata| metadata.modified().ok())
+}
+
+// re-parses the deck file when it changed on disk, keeping the current deck
+// while the file is being edited into an invalid or empty state
+pub fn reload_if_changed(bema: &mut Bema, modified: &mut Option<SystemTime>) -> bool {
+    let new_modified = source_modified(bema);
+    if new_modified == *modified {
+        return false;
+    }
+    *modified = new_modified;
+    match bema.source.as_ref().map(|path| markdown::load(path)) {
+        Some(Ok(mut reloaded)) if !reloaded.slides.is_empty() => {
+            reloaded.theme = bema.theme.clone();
+            reloaded.alternate_theme = bema.alternate_theme.clone();
+            reloaded.highlighting = bema.highlighting.clone();
+            reloaded.code_overflow = bema.code_overflow;
+            // a file without front matter keeps what was set on the deck
+            if reloaded.metadata == Metadata::default() && reloaded.header == DEFAULT_HEADER && reloaded.footer.is_empty() {
+                reloaded.metadata = bema.metadata.clone();
+                reloaded.header = bema.header.clone();
+                reloaded.footer = bema.footer.clone();
+            }
+            *bema = reloaded;
+            true
+        },
+        _ => false,
+    }
+}
+
+// a header or footer template filled in for slide `i`: `{title}`, `{author}`, `{date}`, `{event}` and `{license}`
+// come from the deck metadata, `{slide}` is the slide title, `{n}` its number and `{total}` the number of slides;
+// parts between ` · ` separators that end up empty are left out with their separator
+pub fn chrome(template: &str, bema: &Bema, i: usize) -> String {
+    let metadata = &bema.metadata;
+    let slide = bema.slides.get(i).map(|slide| slide.title.as_str()).unwrap_or("");
+    let fields = [
+        ("{title}", metadata.title.as_str()),
+        ("{author}", metadata.author.as_str()),
+        ("{date}", metadata.date.as_str()),
+        ("{event}", metadata.event.as_str()),
+        ("{license}", metadata.license.as_str()),
+        ("{slide}", slide),
+        ("{n}", &(i + 1).to_string()),
+        ("{total}", &bema.slides.len().to_string()),
+    ];
+    template.split(" · ")
+        .map(|part| fields.iter().fold(part.to_string(), |part, (name, value)| part.replace(name, value)))
+        .filter(|part| !part.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
 
-    Ok(whitespaces)
+// whether slide `i` has a footer line at the bottom, image slides having none
+pub fn has_footer(bema: &Bema, i: usize) -> bool {
+    matches!(bema.slides.get(i), Some(slide) if slide.layout != Layout::Image) && !chrome(&bema.footer, bema, i).is_empty()
+}
+
+// number of pauses in the items, a slide is revealed in that many steps after the first one
+pub fn step_count(items: &[SlideItem]) -> usize {
+    items.iter().map(|item| match item {
+        SlideItem::Pause => 1,
+        SlideItem::Cols { items } | SlideItem::Rows { items } | SlideItem::Framed { items } | SlideItem::Aligned { items, .. } => step_count(items),
+        _ => 0,
+    }).sum()
+}
+
+fn reveal_items(items: &[SlideItem], step: usize, pauses: &mut usize) -> Vec<SlideItem> {
+    items.iter().map(|item| {
+        if *pauses > step {
+            // hidden items keep their place so that columns do not move around
+            return SlideItem::Rows { items: vec![] };
+        }
+        match item {
+            SlideItem::Pause => {
+                *pauses += 1;
+                SlideItem::Pause
+            },
+            SlideItem::Cols { items } => SlideItem::Cols { items: reveal_items(items, step, pauses) },
+            SlideItem::Rows { items } => SlideItem::Rows { items: reveal_items(items, step, pauses) },
+            SlideItem::Framed { items } => SlideItem::Framed { items: reveal_items(items, step, pauses) },
+            SlideItem::Aligned { align, items } => SlideItem::Aligned { align: *align, items: reveal_items(items, step, pauses) },
+            _ => item.clone(),
+        }
+    }).collect()
+}
 
+// the items shown at a given step, everything after the `step + 1`th pause being hidden
+pub fn reveal(items: &[SlideItem], step: usize) -> Vec<SlideItem> {
+    reveal_items(items, step, &mut 0)
 }
 
+// width in terminal cells of each table column
+pub fn table_widths(header: &[String], rows: &[Vec<String>]) -> Vec<usize> {
+    let mut widths: Vec<usize> = header.iter().map(|h| measure::columns(h)).collect();
+    for row in rows {
+        for (k, cell) in row.iter().enumerate() {
+            if k >= widths.len() {
+                widths.push(0);
+            }
+            widths[k] = widths[k].max(measure::columns(cell));
+        }
+    }
+    widths
+}
+
+// the cells of a table row, padded to the column widths according to their alignment
+pub fn align_row(row: &[String], widths: &[usize], align: &[Align]) -> Vec<String> {
+    widths.iter().enumerate().map(|(k, width)| {
+        let text = row.get(k).map(|c| c.as_str()).unwrap_or("");
+        let padding = width.saturating_sub(measure::columns(text));
+        let left = match align.get(k).unwrap_or(&Align::Left) {
+            Align::Left => 0,
+            Align::Center => padding / 2,
+            Align::Right => padding,
+        };
+        format!("{}{}{}", " ".repeat(left), text, " ".repeat(padding - left))
+    }).collect()
+}
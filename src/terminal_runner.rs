@@ -1,156 +1,514 @@
-use crate::runner::{Runner, get_justify, fit_image_bytes};
+use crate::runner::{Runner, decode_image, fit_image, scale_image_size, png_bytes, source_modified, reload_if_changed, table_widths, align_row, step_count, reveal, Highlighting, load_highlighting, chrome, has_footer};
+use crate::cell_buffer::{Cell, CellBuffer, ImagePlacement, text_cells};
+use crate::measure;
+use crate::markup::{self, Span};
 
-use crate::bema::{Bema, SlideItem, Slide};
-use tempfile::Builder;
+use crate::bema::{Bema, SlideItem, Layout, Align, VAlign, ImageSize, CodeOverflow};
+use crate::theme::Theme;
+use crate::error::Result;
+use crate::navigation::{Navigator, Navigation, Key};
+use crate::graphics::{self, Protocol};
 use std::io::{stdout, Write};
-use std::process::Command;
-use std::env;
-use blockish::render_image;
-
-use syntect::easy::HighlightLines;
-use syntect::parsing::SyntaxSet;
-use syntect::highlighting::{ThemeSet, Style};
-use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
+use std::panic;
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use image::{DynamicImage, GenericImageView};
 
 use crossterm::{
-    execute,
-    cursor::{MoveTo, MoveRight, Hide, Show},
+    execute, queue,
+    cursor::{MoveTo, Hide, Show},
     event::{Event, KeyCode, KeyEvent},
     style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor, Attribute, SetAttribute},
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, Clear, ClearType, enable_raw_mode, disable_raw_mode, self},
-    ExecutableCommand, Result,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, enable_raw_mode, disable_raw_mode, self},
     event,
 };
 
+pub struct TerminalRunner {
+    pub presenter: bool,
+    pub start: usize,
+    // detected when None
+    pub graphics: Option<Protocol>,
+}
 
-fn justify_center(size: usize, text: Vec<&String>) -> Result<()> {
-    let whitespaces = get_justify(size, text)?;
-    stdout()
-        .execute(MoveRight(whitespaces as u16))?;
-    Ok(())
+fn to_color(r: u8, g: u8, b: u8, a: u8) -> Option<Color> {
+    if a < 128 { None } else { Some(Color::Rgb { r, g, b }) }
 }
 
-pub struct TerminalRunner {
+// draws an image with half blocks: each cell shows two vertically stacked pixels
+fn image_cells(img: &DynamicImage, columns: usize, rows: usize) -> CellBuffer {
+    let img = img.resize_exact(columns as u32, (rows * 2) as u32, image::imageops::FilterType::Triangle).to_rgba8();
+    let mut buffer = CellBuffer::new(columns);
+    for y in 0..rows {
+        let line = (0..columns).map(|x| {
+            let top = img.get_pixel(x as u32, (2 * y) as u32);
+            let bottom = img.get_pixel(x as u32, (2 * y + 1) as u32);
+            match (to_color(top[0], top[1], top[2], top[3]), to_color(bottom[0], bottom[1], bottom[2], bottom[3])) {
+                (None, None) => Cell::blank(),
+                (None, bottom) => Cell { fg: bottom, ..Cell::new('▄') },
+                (top, bottom) => Cell { fg: top, bg: bottom, ..Cell::new('▀') },
+            }
+        }).collect();
+        buffer.push_line(line);
+    }
+    buffer
 }
 
+fn rgb((r, g, b): (u8, u8, u8)) -> Color {
+    Color::Rgb { r, g, b }
+}
 
-fn display_image(image_path: &String) {
-    match env::var("KITTY_WINDOW_ID") {
-        Ok(_) => {
-            let _res = Command::new("kitty")
-                .arg("+kitten")
-                .arg("icat")
-                .arg(image_path)
-                .output();
-        },
-        Err(_) => {
-            render_image(image_path, 100 * 4);
-        }
+fn span_cells(offset: usize, spans: &[Span], theme: &Theme) -> Vec<Cell> {
+    let mut cells = vec![Cell::blank(); offset];
+    for span in spans {
+        let style = span.style;
+        let fg = style.color.or(if style.code { Some(theme.accent) } else { None }).map(rgb);
+        cells.extend(text_cells(&span.text, &Cell {
+            fg, bold: style.bold, italic: style.italic, underline: style.underline,
+            ..Cell::blank()
+        }));
     }
+    cells
 }
 
+fn navigation_key(code: KeyCode) -> Option<Key> {
+    match code {
+        KeyCode::Char(c) => Some(Key::Char(c)),
+        KeyCode::Enter => Some(Key::Enter),
+        KeyCode::Esc => Some(Key::Escape),
+        KeyCode::Backspace => Some(Key::Backspace),
+        _ => None,
+    }
+}
 
-impl TerminalRunner {
-    fn clear_screen(&self) -> Result<()> {
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(stdout(), ResetColor, Show, LeaveAlternateScreen);
+}
+
+// puts the terminal back in its normal state when dropped, that is on return, on error and on panic;
+// the panic hook restores it before the panic message is printed, so that the message is not lost
+// on the alternate screen; the hook stays installed, so it only restores while a guard is alive
+struct TerminalGuard;
+
+static TERMINAL_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl TerminalGuard {
+    fn new() -> Result<TerminalGuard> {
+        static PANIC_HOOK: Once = Once::new();
+        PANIC_HOOK.call_once(|| {
+            let hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                if TERMINAL_TAKEN.load(Ordering::SeqCst) {
+                    restore_terminal();
+                }
+                hook(info);
+            }));
+        });
+        execute!(stdout(), EnterAlternateScreen, Hide)?;
+        TERMINAL_TAKEN.store(true, Ordering::SeqCst);
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        TERMINAL_TAKEN.store(false, Ordering::SeqCst);
+        restore_terminal();
+    }
+}
+
+enum Input {
+    Key(KeyCode),
+    Resize,
+    // the deck file changed on disk and was parsed again
+    Reload,
+    // half a second went by, for the presenter clock
+    Tick,
+}
+
+// what the terminal currently shows, so that a frame only writes the cells that changed
+struct Screen {
+    size: (usize, usize),
+    lines: Vec<Vec<Cell>>,
+    images: Vec<ImagePlacement>,
+    protocol: Protocol,
+}
+
+impl Screen {
+    fn new(protocol: Protocol) -> Screen {
+        Screen { size: (0, 0), lines: vec![], images: vec![], protocol }
+    }
+
+    // cells without colours are drawn with the theme text and background colours
+    fn draw(&mut self, buffer: &CellBuffer, theme: &Theme) -> Result<()> {
+        let (width, rows) = terminal::size()?;
+        let size = (width as usize, rows as usize);
+        if size != self.size {
+            // after a resize the terminal content cannot be trusted, everything is written again
+            self.size = size;
+            self.lines.clear();
+        }
+        // images that do not fit are cut at the bottom of the screen, drawing them whole would scroll the terminal
+        let mut images = vec![];
+        for image in buffer.images.iter().filter(|image| image.y < size.1) {
+            images.push(clip_image(image, size.1 - image.y)?);
+        }
+        let images_changed = images != self.images;
+        let mut out = stdout();
+        if images_changed && self.protocol == Protocol::Kitty {
+            out.write_all(graphics::kitty_clear())?;
+        } else if images_changed {
+            // other protocols draw images in the cells, they are only erased by writing the cells again
+            self.lines.clear();
+        }
 
-        stdout()
-            .execute(Clear(ClearType::All))?;
+        let (fg, bg) = (rgb(theme.text), rgb(theme.background));
+        let lines: Vec<Vec<Cell>> = (0..size.1).map(|y| (0..size.0).map(|x| {
+            match buffer.lines.get(y).and_then(|line| line.get(x)) {
+                Some(cell) => Cell { fg: cell.fg.or(Some(fg)), bg: cell.bg.or(Some(bg)), ..cell.clone() },
+                None => Cell { fg: Some(fg), bg: Some(bg), ..Cell::blank() },
+            }
+        }).collect()).collect();
 
+        let mut cursor = None;
+        let mut style = None;
+        for (y, line) in lines.iter().enumerate() {
+            for (x, cell) in line.iter().enumerate() {
+                if cell.is_wide_tail() || self.lines.get(y).and_then(|previous| previous.get(x)) == Some(cell) {
+                    continue;
+                }
+                if cursor != Some((x, y)) {
+                    queue!(out, MoveTo(x as u16, y as u16))?;
+                }
+                if style != Some((cell.fg, cell.bg, cell.bold, cell.italic, cell.underline)) {
+                    style = Some((cell.fg, cell.bg, cell.bold, cell.italic, cell.underline));
+                    queue!(out, SetAttribute(Attribute::Reset))?;
+                    queue!(out, SetForegroundColor(cell.fg.unwrap()), SetBackgroundColor(cell.bg.unwrap()))?;
+                    if cell.bold { queue!(out, SetAttribute(Attribute::Bold))?; }
+                    if cell.italic { queue!(out, SetAttribute(Attribute::Italic))?; }
+                    if cell.underline { queue!(out, SetAttribute(Attribute::Underlined))?; }
+                }
+                queue!(out, Print(cell.symbol))?;
+                cursor = Some((x + measure::char_columns(cell.symbol).max(1), y));
+            }
+        }
+        queue!(out, SetAttribute(Attribute::Reset))?;
+        if images_changed {
+            for image in &images {
+                queue!(out, MoveTo(image.x as u16, image.y as u16))?;
+                out.write_all(&graphics::encode(self.protocol, &image.png, image.width, image.height)?)?;
+            }
+            self.images = images;
+        }
+        out.flush()?;
+        self.lines = lines;
         Ok(())
     }
+}
+
+// an image drawn in an area of `width` x `rows` cells, as tall as it is drawn
+fn image_buffer(image: &[u8], extension: &str, size: &ImageSize, (width, rows): (usize, usize), align: Align, protocol: Protocol) -> Result<CellBuffer> {
+    let mut buffer = CellBuffer::new(width);
+    let (cell_width, cell_height) = graphics::cell_size();
+    let area = ((width * cell_width) as f64, (rows * cell_height) as f64);
+    let size = scale_image_size(size, (terminal::size()?.0 as usize * cell_width) as f64);
+    let img = fit_image(&decode_image(image, extension)?, &size, area);
+    let columns = ((img.width() as usize + cell_width / 2) / cell_width).min(width).max(1);
+    let rows = ((img.height() as usize + cell_height / 2) / cell_height).max(1);
+    let x = measure::offset(align, width.saturating_sub(columns) as f64) as usize;
+    if protocol != Protocol::Blocks {
+        for _ in 0..rows {
+            buffer.push_line(vec![]);
+        }
+        buffer.images.push(ImagePlacement { x, y: 0, width: columns, height: rows, png: png_bytes(&img)? });
+    } else {
+        buffer.place(image_cells(&img, columns, rows), x, 0);
+    }
+    Ok(buffer)
+}
 
-    fn read_keycode(&self) -> Result<KeyCode> {
+// the top `rows` rows of an image
+fn clip_image(image: &ImagePlacement, rows: usize) -> Result<ImagePlacement> {
+    if image.height <= rows {
+        return Ok(image.clone());
+    }
+    let img = image::load_from_memory(&image.png)?;
+    let height = (img.height() as usize * rows / image.height).max(1) as u32;
+    Ok(ImagePlacement { height: rows, png: png_bytes(&img.crop_imm(0, 0, img.width(), height))?, ..image.clone() })
+}
+
+impl TerminalRunner {
+    // waits for a key or a resize, waking up every half second to check the deck file and update the clock
+    fn read_input(&self, bema: &mut Bema, modified: &mut Option<SystemTime>) -> Result<Input> {
+        enable_raw_mode()?;
+        let input = loop {
+            if (self.presenter || bema.source.is_some()) && !event::poll(Duration::from_millis(500))? {
+                if reload_if_changed(bema, modified) {
+                    break Input::Reload;
+                }
+                if self.presenter {
+                    break Input::Tick;
+                }
+                continue;
+            }
+            match event::read()? {
+                Event::Key(KeyEvent { code, .. }) => break Input::Key(code),
+                Event::Resize(_, _) => break Input::Resize,
+                _ => {},
+            }
+        };
+        disable_raw_mode()?;
+        Ok(input)
+    }
+
+    // None when the terminal was resized
+    fn read_key(&self) -> Result<Option<KeyCode>> {
         enable_raw_mode()?;
+        let key = loop {
+            match event::read()? {
+                Event::Key(KeyEvent { code, .. }) => break Some(code),
+                Event::Resize(_, _) => break None,
+                _ => {},
+            }
+        };
+        disable_raw_mode()?;
+        Ok(key)
+    }
+
+    // the list of slide titles, the current slide being marked and the selected one highlighted
+    fn render_overview(&self, bema: &Bema, current: usize, selected: usize) -> Result<CellBuffer> {
+        let (width, rows) = terminal::size()?;
+        let (width, rows) = (width as usize, rows as usize);
+        let theme = &bema.theme;
+        let mut buffer = CellBuffer::new(width);
+        buffer.push_text(0, "overview: j/k to select, enter to go, esc to close", Some(rgb(theme.progress)), None, false);
+        buffer.push_line(vec![]);
+        // keeps the selection in the middle of the list when the deck does not fit
+        let visible = rows.saturating_sub(2).max(1);
+        let offset = selected.saturating_sub(visible / 2).min(bema.slides.len().saturating_sub(visible));
+        for (k, slide) in bema.slides.iter().enumerate().skip(offset).take(visible) {
+            let marker = if k == current { '>' } else { ' ' };
+            let line = format!("{} {:>3}. {:<width$}", marker, k + 1, slide.title, width = width);
+            if k == selected {
+                buffer.push_text(0, &line, Some(rgb(theme.background)), Some(rgb(theme.accent)), true);
+            } else {
+                buffer.push_text(0, &line, None, None, false);
+            }
+        }
+        Ok(buffer)
+    }
+
+    // lets the user pick a slide from the list of titles, None when the overview is closed without choosing
+    fn overview(&self, bema: &Bema, current: usize, screen: &mut Screen) -> Result<Option<usize>> {
+        let last = bema.slides.len() - 1;
+        let mut selected = current;
         loop {
-            if let Event::Key(KeyEvent {
-                code,
-                ..
-            }) = event::read()?
-            {
-                disable_raw_mode()?;
-                return Ok(code);
+            screen.draw(&self.render_overview(bema, current, selected)?, &bema.theme)?;
+            let page = (terminal::size()?.1 as usize).saturating_sub(2).max(1);
+            let key = match self.read_key()? {
+                Some(key) => key,
+                None => continue,
+            };
+            match key {
+                KeyCode::Char('j') | KeyCode::Down => selected = (selected + 1).min(last),
+                KeyCode::Char('k') | KeyCode::Up => selected = selected.saturating_sub(1),
+                KeyCode::PageDown => selected = (selected + page).min(last),
+                KeyCode::PageUp => selected = selected.saturating_sub(page),
+                KeyCode::Char('g') | KeyCode::Home => selected = 0,
+                KeyCode::Char('G') | KeyCode::End => selected = last,
+                KeyCode::Enter => return Ok(Some(selected)),
+                KeyCode::Esc | KeyCode::Char('o') | KeyCode::Char('q') => return Ok(None),
+                _ => {},
             }
         }
     }
 
-    fn render_item(&self, item: &SlideItem) -> Result<()> {
+    #[allow(clippy::too_many_arguments)]
+    fn render_item(&self, item: &SlideItem, width: usize, theme: &Theme, highlighting: &Highlighting, overflow: CodeOverflow, align: Align, protocol: Protocol) -> Result<CellBuffer> {
+        let mut buffer = CellBuffer::new(width);
         match item {
-            SlideItem::Image { image, extension, width } => {
-                let bytes = fit_image_bytes(image, width, extension);
-                let mut file = Builder::new()
-                    .prefix("image")
-                    .suffix(".png")
-                    .rand_bytes(5)
-                    .tempfile()?;
-                file.write(&bytes[..])?;
-                display_image(&file.path().to_str().unwrap().to_string());
+            // a column too narrow to hold even one cell of an image
+            SlideItem::Image { .. } if width == 0 => {},
+            SlideItem::Image { image, extension, size } => {
+                // the slide below its index and title lines
+                let rows = (terminal::size()?.1 as usize).saturating_sub(3);
+                buffer = image_buffer(image, extension, size, (width, rows), align, protocol)?;
             },
             SlideItem::Code { extension, source } => {
-                let ps = SyntaxSet::load_defaults_newlines();
-                let ts = ThemeSet::load_defaults();
-
-                let syntax = ps.find_syntax_by_extension(extension).unwrap();
-                let mut h = HighlightLines::new(syntax, &ts.themes["base16-ocean.dark"]);
-                let splits = source.split("\n").map( |x| x.to_string()).collect::<Vec<_>>();
-                let v2: Vec<&String> = splits.iter().map(|s| s).collect::<Vec<&String>>();
-                let whitespaces = get_justify(terminal::size()?.0 as usize, v2)? as usize;
-                for line in LinesWithEndings::from(source) {
-                    let ranges: Vec<(Style, &str)> = h.highlight(line, &ps);
-                    let escaped = as_24_bit_terminal_escaped(&ranges[..], true);
-                    stdout()
-                        .execute(ResetColor)?
-                        .execute(MoveRight(whitespaces as u16))?;
-                    print!("{}", escaped);
-                }
-
-                stdout()
-                    .execute(ResetColor)?;
-                },
-                SlideItem::Text { text } => {
-                    let splits = text.split("\n").map( |x| x.to_string()).collect::<Vec<_>>();
-                    let v2: Vec<&String> = splits.iter().map(|s| s).collect::<Vec<&String>>();
-                    let whitespaces = get_justify(terminal::size()?.0 as usize, v2)?;
-                    for split in splits {
-                        stdout().execute(MoveRight(whitespaces as u16))?;
-                        println!("{}", split);
-                    }
-                },
-                SlideItem::Cols { items } => {
-                    for item2 in items {
-                        self.render_item(item2)?;
-                    }
-                },
-                SlideItem::Rows { items } => {
-                    for item2 in items {
-                        self.render_item(item2)?;
-                    }
-                },
-                SlideItem::Framed { items } => {
-                    for item2 in items {
-                        self.render_item(item2)?;
+                let mut lines = vec![];
+                for ranges in highlighting.highlight(extension, source, theme).iter() {
+                    let ranges = ranges.iter().map(|(style, text)| (*style, text.replace("\t", "    "))).collect::<Vec<_>>();
+                    lines.extend(measure::fit_code(&ranges, width as f64, overflow, |t| measure::columns(t) as f64));
+                }
+                let plains = lines.iter().map(|ranges| ranges.iter().map(|(_, text)| text.as_str()).collect::<String>()).collect::<Vec<_>>();
+                let whitespaces = measure::justify_columns(align, width, &plains);
+                for ranges in lines {
+                    let mut cells = vec![Cell::blank(); whitespaces];
+                    for (style, text) in ranges {
+                        let fg = Some(Color::Rgb { r: style.foreground.r, g: style.foreground.g, b: style.foreground.b });
+                        let bg = Some(Color::Rgb { r: style.background.r, g: style.background.g, b: style.background.b });
+                        cells.extend(text_cells(&text, &Cell { fg, bg, ..Cell::blank() }));
                     }
-                },
+                    buffer.push_line(cells);
+                }
+            },
+            SlideItem::Text { text } => {
+                let lines = markup::parse(&text.replace("\t", "    ")).iter()
+                    .flat_map(|spans| markup::wrap(spans, width as f64, |t| measure::columns(t) as f64))
+                    .collect::<Vec<_>>();
+                let plains = lines.iter().map(|spans| markup::plain(spans)).collect::<Vec<_>>();
+                let whitespaces = measure::justify_columns(align, width, &plains);
+                for spans in lines {
+                    buffer.push_line(span_cells(whitespaces, &spans, theme));
+                }
+            },
+            SlideItem::Cols { items } => {
+                let column_width = width / items.len().max(1);
+                let mut columns = vec![];
+                for item2 in items {
+                    columns.push(self.render_item(item2, column_width, theme, highlighting, overflow, align, protocol)?);
+                }
+                buffer = CellBuffer::merge_columns(columns, column_width, width);
+            },
+            SlideItem::Rows { items } => {
+                if items.len() == 1 {
+                    return self.render_item(&items[0], width, theme, highlighting, overflow, align, protocol);
+                }
+                for item2 in items {
+                    buffer.append(self.render_item(item2, width, theme, highlighting, overflow, align, protocol)?);
+                }
+            },
+            SlideItem::Framed { items } => {
+                let mut content = CellBuffer::new(width.saturating_sub(4));
+                for item2 in items {
+                    content.append(self.render_item(item2, content.width, theme, highlighting, overflow, align, protocol)?);
+                }
+                buffer = content.framed(Some(rgb(theme.frame))).aligned(width, align);
+            },
+            SlideItem::Aligned { align, items } => {
+                for item2 in items {
+                    buffer.append(self.render_item(item2, width, theme, highlighting, overflow, *align, protocol)?);
+                }
+            },
+            SlideItem::Table { header, rows, align: column_align } => {
+                let widths = table_widths(header, rows);
+                let mut lines = vec![];
+                if !header.is_empty() {
+                    lines.push(align_row(header, &widths, column_align));
+                }
+                lines.extend(rows.iter().map(|row| align_row(row, &widths, column_align)));
+                buffer = CellBuffer::table(lines, !header.is_empty(), Some(rgb(theme.frame))).aligned(width, align);
+            },
+            SlideItem::Pause => {},
         }
-        Ok(())
+        Ok(buffer)
     }
 
-    fn render_slide(&self, slide: &Slide) -> Result<()> {
+    fn render_slide(&self, bema: &Bema, i: usize, step: usize, highlighting: &Highlighting, protocol: Protocol) -> Result<CellBuffer> {
+        let slide = &bema.slides[i];
+        let (width, rows) = terminal::size()?;
+        let (width, rows) = (width as usize, rows as usize);
+        let mut buffer = CellBuffer::new(width);
+        let theme = &bema.theme;
 
-        justify_center(terminal::size()?.0 as usize, vec![&slide.title])?;
+        if slide.layout == Layout::Image {
+            // without index, title or footer, images are sized against the whole screen
+            for item in &slide.items {
+                buffer.append(match item {
+                    SlideItem::Image { image, extension, size } => image_buffer(image, extension, size, (width, rows), slide.align, protocol)?,
+                    _ => self.render_item(item, width, theme, highlighting, bema.code_overflow, slide.align, protocol)?,
+                });
+            }
+            return Ok(buffer);
+        }
 
-        stdout()
-        .execute(SetAttribute(Attribute::Bold))?
-        .execute(SetForegroundColor(Color::Blue))?
-        .execute(SetBackgroundColor(Color::Black))?
-        .execute(Print(slide.title.to_string()))?
-        .execute(ResetColor)?
-        .execute(Print("\n\n"))?;
+        buffer.push_text(0, &chrome(&bema.header, bema, i), Some(rgb(theme.progress)), None, false);
+        let footer = chrome(&bema.footer, bema, i);
+        // the last line is kept for the footer
+        let rows = if footer.is_empty() { rows } else { rows.saturating_sub(1) };
 
-        for item in &slide.items {
-            self.render_item(item)?;
+        let render = |items: &[SlideItem]| -> Result<CellBuffer> {
+            let mut content = CellBuffer::new(width);
+            content.push_text(measure::justify_columns(slide.align, width, &[&slide.title]), &slide.title, Some(rgb(theme.title)), None, true);
+            if let Layout::Title { subtitle, byline } = &slide.layout {
+                for line in subtitle.lines() {
+                    let mut cells = vec![Cell::blank(); measure::justify_columns(slide.align, width, &[line])];
+                    cells.extend(text_cells(line, &Cell { fg: Some(rgb(theme.accent)), italic: true, ..Cell::blank() }));
+                    content.push_line(cells);
+                }
+                if !byline.is_empty() {
+                    content.push_line(vec![]);
+                }
+                for line in byline.lines() {
+                    content.push_text(measure::justify_columns(slide.align, width, &[line]), line, Some(rgb(theme.progress)), None, false);
+                }
+            }
+            content.push_line(vec![]);
+            for item in items {
+                content.append(self.render_item(item, width, theme, highlighting, bema.code_overflow, slide.align, protocol)?);
+            }
+            Ok(content)
+        };
+        // placed by the height of the whole slide, so that it does not move as its steps are revealed
+        let content = render(&reveal(&slide.items, step))?;
+        let top = match slide.valign {
+            VAlign::Top => 0,
+            _ => measure::offset_vertical(slide.valign, rows.saturating_sub(1 + render(&slide.items)?.height()) as f64) as usize,
+        };
+        buffer.place(content, 0, 1 + top);
+        if !footer.is_empty() {
+            let mut line = CellBuffer::new(width);
+            line.push_text(measure::justify_columns(Align::Center, width, &[&footer]), &footer, Some(rgb(theme.progress)), None, false);
+            buffer.place(line, 0, rows);
         }
 
-        Ok(())
+        Ok(buffer)
+    }
+
+    fn render_presenter(&self, bema: &Bema, i: usize, start: &Instant, width: usize) -> CellBuffer {
+        let slide = &bema.slides[i];
+        let theme = &bema.theme;
+        let next = match bema.slides.get(i + 1) {
+            Some(next) => format!("next: {}", next.title),
+            None => "next: (end)".to_string(),
+        };
+        let elapsed = start.elapsed().as_secs();
+        let clock = format!("{:02}:{:02}:{:02}", elapsed / 3600, (elapsed / 60) % 60, elapsed % 60);
+        // lines are as wide as the terminal so that they hide the slide behind them
+        let pad = |text: &str| format!("{:<width$}", text, width = width);
+        let mut buffer = CellBuffer::new(width);
+        buffer.push_text(0, &"─".repeat(width), Some(rgb(theme.progress)), None, false);
+        buffer.push_text(0, &pad(&next), Some(rgb(theme.accent)), None, false);
+        for line in slide.notes.split("\n") {
+            buffer.push_text(0, &pad(line), None, None, false);
+        }
+        buffer.push_text(0, &pad(""), None, None, false);
+        let mut clock_buffer = CellBuffer::new(clock.len());
+        clock_buffer.push_text(0, &clock, None, None, true);
+        buffer.place(clock_buffer, width.saturating_sub(clock.len() + 1), 1);
+        buffer
+    }
+
+    // the slide with the presenter notes and the navigation status drawn over it
+    fn render_screen(&self, bema: &Bema, i: usize, slide: &CellBuffer, status: Option<String>, start: &Instant) -> Result<CellBuffer> {
+        let (width, rows) = terminal::size()?;
+        let (width, rows) = (width as usize, rows as usize);
+        let mut buffer = slide.clone();
+        // the status line goes above the footer, and the presenter notes above both
+        let rows = if has_footer(bema, i) { rows.saturating_sub(1) } else { rows };
+        let rows = if status.is_some() { rows.saturating_sub(1) } else { rows };
+        if self.presenter {
+            let presenter = self.render_presenter(bema, i, start, width);
+            let top = rows.saturating_sub(presenter.height());
+            buffer.place(presenter, 0, top);
+        }
+        if let Some(status) = status {
+            let mut line = CellBuffer::new(width);
+            line.push_text(0, &status, Some(rgb(bema.theme.accent)), None, false);
+            buffer.place(line, 0, rows);
+        }
+        Ok(buffer)
     }
 
 }
@@ -158,51 +516,89 @@ impl TerminalRunner {
 impl Runner for TerminalRunner {
 
     fn run(&self, bema: &Bema) -> Result<()> {
-        execute!(stdout(), EnterAlternateScreen)?;
-        self.clear_screen()?;
-
-        execute!(
-            stdout(),
-            Hide
-        )?;
+        let highlighting = load_highlighting(bema)?;
+        // asked before the alternate screen, while the terminal is in a known state
+        let protocol = graphics::detect(self.graphics);
+        let _guard = TerminalGuard::new()?;
+        let mut screen = Screen::new(protocol);
 
-        let mut i : i16 = -1;
+        let mut bema = bema.clone();
+        let mut modified = source_modified(&bema);
+        let start = Instant::now();
+        let mut i : i16 = self.start as i16;
+        let mut step = 0;
+        let mut navigator = Navigator::new();
+        // the current slide rendered at the current size, kept while only the clock changes
+        let mut slide = None;
         loop {
-            if i >= 0 {
-                let c = self.read_keycode()?;
-                match c {
-                    KeyCode::Char('g') => i = 0,
-                    KeyCode::Char('G') => i = bema.slides.len() as i16 - 1,
-                    KeyCode::Char('n')|KeyCode::Char('j')|KeyCode::Char('l')|KeyCode::Right|KeyCode::Down => i+=1,
-                    KeyCode::Char('p')|KeyCode::Char('k')|KeyCode::Char('h')|KeyCode::Left|KeyCode::Up => i-=1,
+            if slide.is_none() {
+                slide = Some(self.render_slide(&bema, i as usize, step, &highlighting, protocol)?);
+            }
+            let frame = self.render_screen(&bema, i as usize, slide.as_ref().unwrap(), navigator.status(), &start)?;
+            screen.draw(&frame, &bema.theme)?;
+
+            let c = match self.read_input(&mut bema, &mut modified)? {
+                Input::Key(c) => c,
+                Input::Tick => continue,
+                Input::Resize => {
+                    slide = None;
+                    continue;
+                },
+                Input::Reload => {
+                    i = i.min(bema.slides.len() as i16 - 1);
+                    step = step.min(step_count(&bema.slides[i as usize].items));
+                    slide = None;
+                    continue;
+                },
+            };
+            slide = None;
+            let mut backwards = false;
+            let navigation = match navigation_key(c) {
+                Some(key) => navigator.key(&bema, i as usize, key),
+                None => Navigation::Ignored,
+            };
+            match navigation {
+                Navigation::Goto(k) => { i = k as i16; step = 0 },
+                Navigation::Consumed => {},
+                Navigation::Ignored => match c {
+                    KeyCode::Char('g') => { i = 0; step = 0 },
+                    KeyCode::Char('G') => { i = bema.slides.len() as i16 - 1; step = 0 },
+                    KeyCode::Char('n')|KeyCode::Char('j')|KeyCode::Char('l')|KeyCode::Right|KeyCode::Down => {
+                        if step < step_count(&bema.slides[i as usize].items) {
+                            step += 1;
+                        } else {
+                            i += 1;
+                            step = 0;
+                        }
+                    },
+                    KeyCode::Char('p')|KeyCode::Char('k')|KeyCode::Char('h')|KeyCode::Left|KeyCode::Up => {
+                        if step > 0 {
+                            step -= 1;
+                        } else {
+                            i -= 1;
+                            backwards = true;
+                        }
+                    },
+                    KeyCode::Char('o') => if let Some(k) = self.overview(&bema, i as usize, &mut screen)? {
+                        i = k as i16;
+                        step = 0;
+                    },
+                    KeyCode::Char('c') => std::mem::swap(&mut bema.theme, &mut bema.alternate_theme),
                     KeyCode::Char('q') => break,
                     _ => {}
                 }
-                if i as usize >= bema.slides.len() {
-                    i = 0;
-                }
-                if i < 0 {
-                    i = bema.slides.len() as i16 - 1;
-                }
-            } else {
+            }
+            if i as usize >= bema.slides.len() {
                 i = 0;
             }
-            execute!(
-                stdout(),
-                MoveTo(0, 0),
-            )?;
-            self.clear_screen()?;
-            println!("{}/{}", i + 1, bema.slides.len());
-            self.render_slide(bema.slides.get(i as usize).unwrap())?;
+            if i < 0 {
+                i = bema.slides.len() as i16 - 1;
+            }
+            if backwards {
+                step = step_count(&bema.slides[i as usize].items);
+            }
         }
 
-        execute!(
-            stdout(),
-            Show
-        )?;
-
-        execute!(stdout(), LeaveAlternateScreen)?;
-
         Ok(())
     }
 }
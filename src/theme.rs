@@ -0,0 +1,87 @@
+#[derive(Clone, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    pub title: (u8, u8, u8),
+    pub text: (u8, u8, u8),
+    // inline code and highlights such as the next slide in presenter mode
+    pub accent: (u8, u8, u8),
+    pub background: (u8, u8, u8),
+    // slide counter and progress bar
+    pub progress: (u8, u8, u8),
+    // frame and table borders
+    pub frame: (u8, u8, u8),
+    // name of a syntect theme used to highlight code
+    pub code_theme: String,
+}
+
+impl Theme {
+    pub fn dark() -> Theme {
+        Theme {
+            name: "dark".to_string(),
+            title: (80, 120, 255),
+            text: (255, 255, 255),
+            accent: (240, 200, 60),
+            background: (0, 0, 0),
+            progress: (80, 80, 80),
+            frame: (255, 255, 255),
+            code_theme: "base16-ocean.dark".to_string(),
+        }
+    }
+
+    pub fn light() -> Theme {
+        Theme {
+            name: "light".to_string(),
+            title: (30, 70, 200),
+            text: (0, 0, 0),
+            accent: (200, 80, 0),
+            background: (255, 255, 255),
+            progress: (200, 200, 200),
+            frame: (0, 0, 0),
+            code_theme: "InspiredGitHub".to_string(),
+        }
+    }
+
+    pub fn solarized_dark() -> Theme {
+        Theme {
+            name: "solarized-dark".to_string(),
+            title: (38, 139, 210),
+            text: (147, 161, 161),
+            accent: (181, 137, 0),
+            background: (0, 43, 54),
+            progress: (88, 110, 117),
+            frame: (101, 123, 131),
+            code_theme: "Solarized (dark)".to_string(),
+        }
+    }
+
+    pub fn solarized_light() -> Theme {
+        Theme {
+            name: "solarized-light".to_string(),
+            title: (38, 139, 210),
+            text: (88, 110, 117),
+            accent: (203, 75, 22),
+            background: (253, 246, 227),
+            progress: (147, 161, 161),
+            frame: (101, 123, 131),
+            code_theme: "Solarized (light)".to_string(),
+        }
+    }
+
+    pub fn builtin() -> Vec<Theme> {
+        vec![Theme::dark(), Theme::light(), Theme::solarized_dark(), Theme::solarized_light()]
+    }
+
+    pub fn by_name(name: &str) -> Option<Theme> {
+        Theme::builtin().into_iter().find(|theme| theme.name == name)
+    }
+
+    // the built-in theme of the opposite brightness, used by the theme toggle
+    pub fn counterpart(&self) -> Theme {
+        match self.name.as_str() {
+            "light" => Theme::dark(),
+            "solarized-dark" => Theme::solarized_light(),
+            "solarized-light" => Theme::solarized_dark(),
+            _ => Theme::light(),
+        }
+    }
+}